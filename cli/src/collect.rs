@@ -0,0 +1,226 @@
+//! Collect phase: visiting all generation sites before any of them is generated.
+use crate::error::{Location, SourcegenError, SourcegenErrorKind};
+use crate::generate::{
+    body_expr_markers, detect_file_invocation, detect_invocation, is_generated, is_sourcegen_macro,
+    macro_attribute, sourcegen_attr_index, GeneratorInfo,
+};
+use crate::mods::ModResolver;
+use crate::region::Node;
+use crate::session::Session;
+use anyhow::Context;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use syn::{Attribute, File, Item, ItemFn};
+
+/// Generation site visited during the collect phase, see [`SourceGenerator::collect`].
+///
+/// [`SourceGenerator::collect`]: trait.SourceGenerator.html#method.collect
+pub struct CollectedSite<'a> {
+    /// Arguments of the invocation (of this generator's stage, for stacked generators).
+    pub args: syn::AttributeArgs,
+    /// Annotated item, as it is before the generation.
+    pub item: SiteItem<'a>,
+    /// Path of the module the site is in (for example, `crate::a::b`).
+    pub module_path: String,
+    /// Location of the invocation.
+    pub location: Location,
+}
+
+/// Item annotated by the invocation.
+#[derive(Clone, Copy)]
+pub enum SiteItem<'a> {
+    /// Item of a module (or of a function body).
+    Item(&'a Item),
+    /// Item inside the impl block, along with the impl block.
+    ImplItem(&'a syn::ImplItem, &'a syn::ItemImpl),
+    /// Item inside the trait definition, along with the trait.
+    TraitItem(&'a syn::TraitItem, &'a syn::ItemTrait),
+    /// Whole file or inline module content, for `#![sourcegen]` and `sourcegen::generate!` sites
+    /// (the latter are given the content of the enclosing module).
+    File(&'a File),
+    /// Function containing `sourcegen::expr!` marker.
+    Expr(&'a ItemFn),
+}
+
+/// Visit all generation sites of the file (for example, the crate root), following out-of-line
+/// modules. Files that cannot be read or parsed and invalid invocations are skipped (these are
+/// reported while generating them). Files in `visited` are skipped, too (same module could be
+/// reachable from multiple targets).
+pub(crate) fn collect_file(
+    root: &Path,
+    session: &mut Session,
+    mod_resolver: &ModResolver,
+    visited: &mut HashSet<PathBuf>,
+) -> Result<(), SourcegenError> {
+    if !visited.insert(root.to_owned()) {
+        return Ok(());
+    }
+    if let Ok(source) = session.fs.read_to_string(root) {
+        collect_source(root, &source, session, mod_resolver, Some(visited))?;
+    }
+    Ok(())
+}
+
+/// Visit all generation sites of the source file. Out-of-line modules are followed unless
+/// `visited` is `None`.
+pub(crate) fn collect_source(
+    path: &Path,
+    source: &str,
+    session: &mut Session,
+    mod_resolver: &ModResolver,
+    visited: Option<&mut HashSet<PathBuf>>,
+) -> Result<(), SourcegenError> {
+    let mut file = match syn::parse_file(source) {
+        Ok(file) => file,
+        Err(_) => return Ok(()),
+    };
+    let mut collector = Collector {
+        path,
+        session,
+        visited,
+    };
+    match detect_file_invocation(path, &mut file, collector.session) {
+        Ok(Some(invoke)) => {
+            collector.visit(&invoke, &SiteItem::File(&file), mod_resolver)?;
+        }
+        Ok(None) => collector.collect_items(&file.items, &(), mod_resolver)?,
+        Err(_) => {}
+    }
+    Ok(())
+}
+
+struct Collector<'s, 'a> {
+    path: &'s Path,
+    session: &'s mut Session<'a>,
+    /// Files visited so far (if out-of-line modules are followed).
+    visited: Option<&'s mut HashSet<PathBuf>>,
+}
+
+impl Collector<'_, '_> {
+    fn collect_items<N: Node>(
+        &mut self,
+        items: &[N],
+        parent: &N::Parent,
+        mod_resolver: &ModResolver,
+    ) -> Result<(), SourcegenError> {
+        for item in items {
+            let attrs = item.attributes_ref().unwrap_or_default();
+            if is_generated(attrs) {
+                continue;
+            }
+            match item.as_item() {
+                Some(Item::Macro(mac)) if is_sourcegen_macro(mac) => {
+                    let file = File {
+                        shebang: None,
+                        attrs: Vec::new(),
+                        items: items.iter().filter_map(Node::as_item).cloned().collect(),
+                    };
+                    let attrs = [macro_attribute(mac)];
+                    self.visit_attrs(&attrs, &SiteItem::File(&file), mod_resolver)?;
+                }
+                _ => {
+                    if let Some(site) = item.site_item(parent) {
+                        self.visit_attrs(attrs, &site, mod_resolver)?;
+                    }
+                }
+            }
+
+            if let Some(item) = item.as_item() {
+                self.collect_nested(item, mod_resolver)?;
+            }
+            if let Some(block) = item.block() {
+                self.collect_items(&block.stmts, &(), mod_resolver)?;
+                self.collect_exprs(item, mod_resolver)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn collect_nested(
+        &mut self,
+        item: &Item,
+        mod_resolver: &ModResolver,
+    ) -> Result<(), SourcegenError> {
+        match item {
+            Item::Mod(item) => {
+                let nested_mod_resolver = mod_resolver.push_module(&item.ident.to_string());
+                if let Some((_, ref items)) = item.content {
+                    if sourcegen_attr_index(&item.attrs, true).is_some() {
+                        let file = File {
+                            shebang: None,
+                            attrs: Vec::new(),
+                            items: items.clone(),
+                        };
+                        if let Ok(Some(invoke)) =
+                            detect_invocation(self.path, &item.attrs, true, self.session)
+                        {
+                            self.visit(&invoke, &SiteItem::File(&file), &nested_mod_resolver)?;
+                        }
+                    } else {
+                        self.collect_items(items, &(), &nested_mod_resolver)?;
+                    }
+                } else if let Some(ref mut visited) = self.visited {
+                    if let Ok(path) = mod_resolver.resolve_module_file(item, self.session.fs) {
+                        collect_file(&path, self.session, &nested_mod_resolver, visited)?;
+                    }
+                }
+            }
+            Item::Impl(item) => self.collect_items(&item.items, item, mod_resolver)?,
+            Item::Trait(item) => self.collect_items(&item.items, item, mod_resolver)?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Visit `sourcegen::expr!` markers in the body of the function (or method).
+    fn collect_exprs<N: Node>(
+        &mut self,
+        item: &N,
+        mod_resolver: &ModResolver,
+    ) -> Result<(), SourcegenError> {
+        let markers = item.block().map(body_expr_markers).unwrap_or_default();
+        if markers.is_empty() {
+            return Ok(());
+        }
+        let function = item.as_function().unwrap();
+        for marker in markers {
+            let attrs = [marker.attribute()];
+            self.visit_attrs(&attrs, &SiteItem::Expr(&function), mod_resolver)?;
+        }
+        Ok(())
+    }
+
+    fn visit_attrs(
+        &mut self,
+        attrs: &[Attribute],
+        site: &SiteItem,
+        mod_resolver: &ModResolver,
+    ) -> Result<(), SourcegenError> {
+        if let Ok(Some(invoke)) = detect_invocation(self.path, attrs, false, self.session) {
+            self.visit(&invoke, site, mod_resolver)?;
+        }
+        Ok(())
+    }
+
+    /// Give the site to the generator of the invocation (and to every stacked generator).
+    fn visit(
+        &mut self,
+        invoke: &GeneratorInfo,
+        site: &SiteItem,
+        mod_resolver: &ModResolver,
+    ) -> Result<(), SourcegenError> {
+        for stage in std::iter::once(invoke).chain(invoke.pipeline.iter()) {
+            let collected = CollectedSite {
+                args: stage.args.clone(),
+                item: *site,
+                module_path: mod_resolver.module_path(),
+                location: stage.context_location.clone(),
+            };
+            let result = stage.generator.collect(&collected).with_context(|| {
+                SourcegenErrorKind::GeneratorError(stage.context_location.clone())
+            });
+            self.session.recover(result)?;
+        }
+        Ok(())
+    }
+}
@@ -19,6 +19,30 @@ impl Location {
             end: span.end(),
         }
     }
+
+    pub(crate) fn from_path_line_column(path: &Path, line: usize, column: usize) -> Self {
+        let position = LineColumn { line, column };
+        Location {
+            path: path.to_owned(),
+            start: position,
+            end: position,
+        }
+    }
+
+    /// Path to the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Start of the span (line is 1-indexed, column is 0-indexed).
+    pub fn start(&self) -> LineColumn {
+        self.start
+    }
+
+    /// End of the span (line is 1-indexed, column is 0-indexed).
+    pub fn end(&self) -> LineColumn {
+        self.end
+    }
 }
 
 impl fmt::Display for Location {
@@ -35,6 +59,38 @@ impl fmt::Display for Location {
 
 pub type SourcegenError = anyhow::Error;
 
+/// All errors collected during the run in the keep-going mode.
+#[derive(Debug)]
+pub struct SourcegenErrors(Vec<SourcegenError>);
+
+impl SourcegenErrors {
+    pub(crate) fn new(errors: Vec<SourcegenError>) -> Self {
+        SourcegenErrors(errors)
+    }
+
+    /// List of errors, in the order they were encountered. Use `downcast_ref` to get the
+    /// `SourcegenErrorKind` of each error.
+    pub fn errors(&self) -> &[SourcegenError] {
+        &self.0
+    }
+}
+
+impl fmt::Display for SourcegenErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Source generation failed with {} error(s):",
+            self.0.len()
+        )?;
+        for err in &self.0 {
+            write!(f, "\n  {:#}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SourcegenErrors {}
+
 #[derive(Debug, Error, Clone, PartialEq, Eq)]
 pub enum SourcegenErrorKind {
     // Tool errors
@@ -54,9 +110,31 @@ pub enum SourcegenErrorKind {
     GeneratorAttributeMustBeString(Location),
     #[error("{0}: missing `generator` attribute, must be a string (for example, `generator = \"sample_generator\"`)")]
     MissingGeneratorAttribute(Location),
+    #[error("{0}: `header` attribute must be a string or `false` (for example, `header = \"Generated from {{schema}}\"`)")]
+    InvalidHeaderAttribute(Location),
+    #[error("{0}: invalid header template, unknown placeholder or unbalanced braces in `{1}`")]
+    InvalidHeaderTemplate(Location, String),
+    #[error("{0}: invalid generator pipeline, {1}")]
+    InvalidPipeline(Location, String),
+    #[error("{0}: invalid `output` attribute, {1}")]
+    InvalidOutput(Location, String),
+    #[error("{0}: generated module file path `{1}` must be relative, end with `.rs` and consist of valid module names")]
+    InvalidModulePath(Location, String),
+    #[error("{0}: generated module files `{1}` and `{2}` define the same module")]
+    DuplicateModuleFile(Location, String, String),
+    #[error(
+        "{0}: generator '{1}' must produce a list of items for `sourcegen::generate!` invocation"
+    )]
+    InvalidMacroOutput(Location, String),
     #[error("Failed to resolve module '{1}' with a parent module '{0}'")]
     CannotResolveModule(String, String),
 
+    // Write errors
+    #[error("File `{0}` was modified after the changes were planned.")]
+    FileModified(String),
+    #[error("Backup file `{0}` already exists, previous run was interrupted. Restore the file from the backup or remove the backup.")]
+    BackupExists(String),
+
     // Formatter errors
     #[error("`rustfmt` is not installed for the current toolchain. Run `rustup component add rustfmt` to install it.")]
     NoRustFmt,
@@ -67,6 +145,50 @@ pub enum SourcegenErrorKind {
     #[error("`rustfmt` returned an error: {0}")]
     RustFmtError(String),
 
+    #[error("`fixpoint` must allow at least one iteration.")]
+    InvalidFixpoint,
+    #[error("Generation did not converge after {0} iterations, sites still changing: {1}")]
+    NotConverged(usize, String),
+    #[error("Generated code is not idempotent, running the generation again changes: {0}")]
+    NotIdempotent(String),
     #[error("Invalid package names: {0}")]
     InvalidPackageNames(String),
+    #[error("Failed to write report to `{0}`.")]
+    WriteReport(String),
+}
+
+impl SourcegenErrorKind {
+    /// Name of the error kind, used in reports.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            SourcegenErrorKind::MetadataError => "MetadataError",
+            SourcegenErrorKind::ProcessFile(_) => "ProcessFile",
+            SourcegenErrorKind::GeneratorNotFound(_, _) => "GeneratorNotFound",
+            SourcegenErrorKind::GeneratorError(_) => "GeneratorError",
+            SourcegenErrorKind::MultipleGeneratorAttributes(_) => "MultipleGeneratorAttributes",
+            SourcegenErrorKind::GeneratorAttributeMustBeString(_) => {
+                "GeneratorAttributeMustBeString"
+            }
+            SourcegenErrorKind::MissingGeneratorAttribute(_) => "MissingGeneratorAttribute",
+            SourcegenErrorKind::InvalidHeaderAttribute(_) => "InvalidHeaderAttribute",
+            SourcegenErrorKind::InvalidHeaderTemplate(_, _) => "InvalidHeaderTemplate",
+            SourcegenErrorKind::InvalidPipeline(_, _) => "InvalidPipeline",
+            SourcegenErrorKind::InvalidOutput(_, _) => "InvalidOutput",
+            SourcegenErrorKind::InvalidModulePath(_, _) => "InvalidModulePath",
+            SourcegenErrorKind::DuplicateModuleFile(_, _, _) => "DuplicateModuleFile",
+            SourcegenErrorKind::InvalidMacroOutput(_, _) => "InvalidMacroOutput",
+            SourcegenErrorKind::CannotResolveModule(_, _) => "CannotResolveModule",
+            SourcegenErrorKind::FileModified(_) => "FileModified",
+            SourcegenErrorKind::BackupExists(_) => "BackupExists",
+            SourcegenErrorKind::NoRustFmt => "NoRustFmt",
+            SourcegenErrorKind::WhichRustFmtFailed => "WhichRustFmtFailed",
+            SourcegenErrorKind::RustFmtFailed => "RustFmtFailed",
+            SourcegenErrorKind::RustFmtError(_) => "RustFmtError",
+            SourcegenErrorKind::InvalidFixpoint => "InvalidFixpoint",
+            SourcegenErrorKind::NotConverged(_, _) => "NotConverged",
+            SourcegenErrorKind::NotIdempotent(_) => "NotIdempotent",
+            SourcegenErrorKind::InvalidPackageNames(_) => "InvalidPackageNames",
+            SourcegenErrorKind::WriteReport(_) => "WriteReport",
+        }
+    }
 }
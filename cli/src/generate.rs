@@ -1,138 +1,628 @@
 use crate::error::{Location, SourcegenError, SourcegenErrorKind};
+use crate::header::{render_header, FILE_HEADER, ITEM_HEADER};
+use crate::index::{GeneratorContext, ItemIndex};
 use crate::mods::ModResolver;
+use crate::plan::{FileChange, GeneratedRegion};
+use crate::region::Node;
+use crate::report::SiteId;
+use crate::session::Session;
+use crate::vfs::{FileSystem, REAL_FILE_SYSTEM};
 use crate::{GeneratorsMap, SourceGenerator};
 use anyhow::Context;
-use proc_macro2::{LineColumn, TokenStream};
-use std::collections::{BTreeMap, HashMap};
-use std::path::Path;
+use proc_macro2::{Delimiter, Group, LineColumn, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::time::Instant;
 use syn::spanned::Spanned;
-use syn::{Attribute, AttributeArgs, File, Item, LitStr, Meta, NestedMeta};
+use syn::{
+    AttrStyle, Attribute, AttributeArgs, Block, File, Ident, Item, ItemFn, ItemMacro, ItemMod,
+    LitStr, MacroDelimiter, Meta, NestedMeta, Stmt,
+};
 
-static ITEM_COMMENT: &str =
-    "// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.";
-static FILE_COMMENT: &str = "// Generated. All manual edits below this line will be discarded.";
-
-#[derive(Debug, PartialOrd, Ord, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq)]
 struct Region {
     from: usize,
     to: usize,
     indent: usize,
 }
 
+/// Generated content for a single region along with the invocation that generated it.
+struct Expansion {
+    tokens: TokenStream,
+    /// Header comment to add in front of the generated block.
+    header: String,
+    generator: String,
+    location: Location,
+    /// Site in the report (none if this expansion removes an orphaned generated item).
+    site: Option<SiteId>,
+    /// Code to wrap the generated content in for formatting (see `Node::WRAPPER`).
+    wrapper: Option<&'static str>,
+    /// Start the generated code on a new line (the region starts right at the end of the
+    /// invocation, see `Walker::handle_macro_site`).
+    newline: bool,
+    /// Put the closing delimiter following the region on its own line, with the given indent (see
+    /// `Walker::handle_expr_site`).
+    closing_indent: Option<usize>,
+    /// Text to put in front of the generated code (`;` after the arguments of `sourcegen::expr!`
+    /// marker that is not generated yet).
+    prefix: &'static str,
+    /// If the rendered code differs from the current content of the region (set while rendering).
+    changed: bool,
+}
+
 /// Replace a single file with the generated content
 pub fn process_single_file(path: &Path, tokens: TokenStream) -> Result<(), SourcegenError> {
+    process_single_file_with(&REAL_FILE_SYSTEM, path, tokens)
+}
+
+/// Same as [`process_single_file`], but reads and writes the file via the given file system.
+///
+/// [`process_single_file`]: fn.process_single_file.html
+pub fn process_single_file_with(
+    fs: &dyn FileSystem,
+    path: &Path,
+    tokens: TokenStream,
+) -> Result<(), SourcegenError> {
     let formatter = crate::rustfmt::Formatter::new(path.parent().unwrap())?;
 
-    let source = if path.exists() {
-        std::fs::read_to_string(path)
-            .with_context(|| SourcegenErrorKind::ProcessFile(path.display().to_string()))?
-    } else {
-        String::new()
-    };
-    let replacement = Replacement {
-        comment: FILE_COMMENT,
-        is_cr_lf: is_cr_lf(&source),
-        tokens: &tokens,
-    };
-    let output = formatter.format(path, replacement)?;
+    let source = read_or_empty(fs, path)?;
+    let output = render_file(
+        &formatter,
+        path,
+        &source,
+        &format!("// {}", FILE_HEADER),
+        &tokens,
+    )?;
     if source != output {
-        std::fs::write(path, output)
+        fs.write(path, &output)
             .with_context(|| SourcegenErrorKind::ProcessFile(path.display().to_string()))?;
     }
     Ok(())
 }
 
-pub fn process_source_file(
+/// Verify that generating the whole file (as `process_single_file` does) is idempotent: call
+/// `generate` with the current content of the file (empty if it does not exist), render the result,
+/// then call `generate` again with the rendered content and fail with `NotIdempotent` error naming
+/// the `generator` if the second pass changes anything. Nothing is written.
+pub fn verify_single_file<F>(
+    fs: &dyn FileSystem,
+    path: &Path,
+    generator: &str,
+    generate: F,
+) -> Result<(), SourcegenError>
+where
+    F: Fn(&str) -> Result<TokenStream, SourcegenError>,
+{
+    let formatter = crate::rustfmt::Formatter::new(path.parent().unwrap())?;
+
+    let source = read_or_empty(fs, path)?;
+    let header = format!("// {}", FILE_HEADER);
+    let output = render_file(&formatter, path, &source, &header, &generate(&source)?)?;
+    let again = render_file(&formatter, path, &output, &header, &generate(&output)?)?;
+    if output != again {
+        let site = format!("{} (whole file) ({})", path.display(), generator);
+        return Err(SourcegenErrorKind::NotIdempotent(site).into());
+    }
+    Ok(())
+}
+
+/// Read the file, if it exists (files generated from scratch do not exist yet).
+fn read_or_empty(fs: &dyn FileSystem, path: &Path) -> Result<String, SourcegenError> {
+    if !fs.is_file(path) {
+        return Ok(String::new());
+    }
+    fs.read_to_string(path)
+        .with_context(|| SourcegenErrorKind::ProcessFile(path.display().to_string()))
+}
+
+/// Render generated content of the whole file, replacing its current `source`.
+fn render_file(
+    formatter: &crate::rustfmt::Formatter,
+    basefile: &Path,
+    source: &str,
+    header: &str,
+    tokens: &TokenStream,
+) -> Result<String, SourcegenError> {
+    let replacement = Replacement {
+        comment: header,
+        is_cr_lf: is_cr_lf(source),
+        tokens,
+    };
+    formatter.format(basefile, replacement)
+}
+
+/// Render generated code into the separate file given via `output` argument of the invocation.
+/// Returns the tokens to replace the site with: the annotated item followed by the `include!` of the
+/// generated file (wrapped into a module of the same name if the annotated item is a module). Change
+/// to the generated file is returned only if its content is different.
+fn redirect_output(
+    path: &Path,
+    item: Option<&Item>,
+    invoke: &GeneratorInfo,
+    tokens: TokenStream,
+    session: &mut Session,
+) -> Result<(TokenStream, Option<FileChange>), SourcegenError> {
+    let output = invoke.output.as_ref().unwrap();
+    let item = item.ok_or_else(|| {
+        SourcegenErrorKind::InvalidOutput(
+            invoke.context_location.clone(),
+            "only supported for module items, not inside impl blocks, traits or functions"
+                .to_owned(),
+        )
+    })?;
+    let (tokens, link) = match item {
+        Item::Mod(stub) => {
+            // Content of the generated module goes into the file
+            let tokens = match syn::parse2::<File>(tokens.clone()) {
+                Ok(File { items, .. }) => match items.split_first() {
+                    Some((
+                        Item::Mod(ItemMod {
+                            content: Some((_, content)),
+                            ..
+                        }),
+                        rest,
+                    )) => quote::quote!(#(#content)* #(#rest)*),
+                    _ => tokens,
+                },
+                Err(_) => tokens,
+            };
+            let vis = &stub.vis;
+            let ident = &stub.ident;
+            let link = quote::quote! {
+                #[sourcegen::generated]
+                #vis mod #ident {
+                    include!(#output);
+                }
+            };
+            (tokens, link)
+        }
+        _ => (
+            tokens,
+            quote::quote! {
+                #[sourcegen::generated]
+                include!(#output);
+            },
+        ),
+    };
+
+    let output_path = path.parent().unwrap_or_else(|| Path::new("")).join(output);
+    let old = read_or_empty(session.fs, &output_path)?;
+    let header = invocation_header(session, invoke, true)?;
+    // Output directory might not exist yet, so format relative to the annotated file
+    let formatter = crate::rustfmt::Formatter::new(path.parent().unwrap())?;
+    let started = Instant::now();
+    let new = render_file(&formatter, path, &old, &header, &tokens)?;
+    session
+        .report
+        .rustfmt_finished(&output_path, started.elapsed());
+
+    let file = if old != new {
+        Some(FileChange {
+            regions: vec![GeneratedRegion {
+                range: 0..old.len(),
+                generator: invoke.name.clone(),
+                location: invoke.context_location.clone(),
+                changed: true,
+            }],
+            path: output_path,
+            old,
+            new,
+        })
+    } else {
+        None
+    };
+    Ok((quote::quote!(#item #link), file))
+}
+
+/// Render files returned by `SourceGenerator::generate_mod_files`. Returns names of the top-level
+/// modules, to be declared in the module itself, and changes to the files. Declarations of the
+/// files in nested directories are added to their parent module files, which are generated if the
+/// generator did not return them.
+fn render_mod_files(
     path: &Path,
-    generators: &HashMap<&str, &dyn SourceGenerator>,
     mod_resolver: &ModResolver,
+    invoke: &GeneratorInfo,
+    mod_files: Vec<(PathBuf, TokenStream)>,
+    session: &mut Session,
+) -> Result<(BTreeSet<String>, Vec<FileChange>), SourcegenError> {
+    // Module path (as a list of names) to its file and content
+    let mut modules: BTreeMap<Vec<String>, (PathBuf, TokenStream)> = BTreeMap::new();
+    for (file_path, tokens) in mod_files {
+        let module = module_path(&file_path).ok_or_else(|| {
+            SourcegenErrorKind::InvalidModulePath(
+                invoke.context_location.clone(),
+                file_path.display().to_string(),
+            )
+        })?;
+        // `widget.rs` and `widget/mod.rs` are the same module
+        if let Some((previous, _)) = modules.get(&module) {
+            return Err(SourcegenErrorKind::DuplicateModuleFile(
+                invoke.context_location.clone(),
+                previous.display().to_string(),
+                file_path.display().to_string(),
+            )
+            .into());
+        }
+        modules.insert(module, (file_path, tokens));
+    }
+
+    // Declare every module in its parent, generating missing parent modules
+    let mut children: BTreeMap<Vec<String>, BTreeSet<String>> = BTreeMap::new();
+    let mut pending = modules.keys().cloned().collect::<Vec<_>>();
+    while let Some(mut module) = pending.pop() {
+        let name = module.pop().unwrap();
+        if !module.is_empty() && !modules.contains_key(&module) {
+            let file_path = PathBuf::from(format!("{}.rs", module.join("/")));
+            modules.insert(module.clone(), (file_path, TokenStream::new()));
+            pending.push(module.clone());
+        }
+        children.entry(module).or_default().insert(name);
+    }
+    let declarations =
+        |module: &Vec<String>| mod_declarations(children.get(module).into_iter().flatten());
+
+    let header = invocation_header(session, invoke, true)?;
+    let formatter = crate::rustfmt::Formatter::new(path.parent().unwrap())?;
+    let mut changes = Vec::new();
+    for (module, (file_path, tokens)) in &modules {
+        let file_path = mod_resolver.module_dir().join(file_path);
+        let old = read_or_empty(session.fs, &file_path)?;
+        let declarations = declarations(module);
+        let tokens = quote::quote!(#tokens #declarations);
+        let started = Instant::now();
+        let new = render_file(&formatter, path, &old, &header, &tokens)?;
+        session
+            .report
+            .rustfmt_finished(&file_path, started.elapsed());
+        if old != new {
+            changes.push(FileChange {
+                regions: vec![GeneratedRegion {
+                    range: 0..old.len(),
+                    generator: invoke.name.clone(),
+                    location: invoke.context_location.clone(),
+                    changed: true,
+                }],
+                path: file_path,
+                old,
+                new,
+            });
+        }
+    }
+    Ok((children.remove(&Vec::new()).unwrap_or_default(), changes))
+}
+
+/// `pub mod` declarations of the given modules.
+fn mod_declarations<'n>(names: impl Iterator<Item = &'n String>) -> TokenStream {
+    let names = names.map(|name| Ident::new(name, Span::call_site()));
+    quote::quote!(#(pub mod #names;)*)
+}
+
+/// Declarations of the generated modules that are missing among the `items` (generated modules
+/// are added to the handwritten content when `generate_file` returns no changes).
+fn missing_mod_declarations(items: &[Item], names: &BTreeSet<String>) -> TokenStream {
+    let declared = items
+        .iter()
+        .filter_map(|item| match item {
+            Item::Mod(item) if item.content.is_none() => Some(item.ident.to_string()),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+    mod_declarations(names.difference(&declared))
+}
+
+/// Convert relative path of the generated file into the module path: `a/b.rs` and `a/b/mod.rs`
+/// both become `["a", "b"]`. Returns `None` if the path is not a valid module file path.
+fn module_path(path: &Path) -> Option<Vec<String>> {
+    if path.extension()? != "rs" {
+        return None;
+    }
+    let mut module = Vec::new();
+    for component in path.with_extension("").components() {
+        match component {
+            Component::Normal(name) => module.push(name.to_str()?.to_owned()),
+            _ => return None,
+        }
+    }
+    if module.last().is_some_and(|name| name == "mod") {
+        module.pop();
+    }
+    if module.is_empty()
+        || module
+            .iter()
+            .any(|name| syn::parse_str::<Ident>(name).is_err())
+    {
+        return None;
+    }
+    Some(module)
+}
+
+/// Expand the source file and all out-of-line modules it refers to. Every file that needs to be
+/// updated is added to `changes`; no files are written. Files in `visited` are skipped (same module
+/// could be reachable from multiple targets, for example, `lib.rs` and `main.rs`).
+pub fn plan_source_file(
+    path: &Path,
+    session: &mut Session,
+    mod_resolver: &ModResolver,
+    index: &ItemIndex,
+    visited: &mut HashSet<PathBuf>,
+    changes: &mut Vec<FileChange>,
 ) -> Result<(), SourcegenError> {
-    let source = std::fs::read_to_string(path)
+    if !visited.insert(path.to_owned()) {
+        return Ok(());
+    }
+    let mut modules = Vec::new();
+    let result = plan_single_source_file(
+        path,
+        session,
+        mod_resolver,
+        index,
+        &mut modules,
+        visited,
+        changes,
+    );
+    session.recover(result)?;
+
+    for (parent_resolver, item) in modules {
+        let mod_file = parent_resolver.resolve_module_file(&item, session.fs);
+        if let Some(mod_file) = session.recover(mod_file)? {
+            let nested_mod_resolver = parent_resolver.push_module(&item.ident.to_string());
+            plan_source_file(
+                &mod_file,
+                session,
+                &nested_mod_resolver,
+                index,
+                visited,
+                changes,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn plan_single_source_file(
+    path: &Path,
+    session: &mut Session,
+    mod_resolver: &ModResolver,
+    index: &ItemIndex,
+    modules: &mut Vec<(ModResolver, ItemMod)>,
+    visited: &mut HashSet<PathBuf>,
+    changes: &mut Vec<FileChange>,
+) -> Result<(), SourcegenError> {
+    session.observer.file_started(path);
+    let source = session
+        .fs
+        .read_to_string(path)
         .with_context(|| SourcegenErrorKind::ProcessFile(path.display().to_string()))?;
-    let mut file = syn::parse_file(&source)
+    let mut files = Vec::new();
+    let (output, regions) = expand_source(
+        path,
+        &source,
+        session,
+        mod_resolver,
+        index,
+        modules,
+        &mut files,
+    )?;
+    if session.verify {
+        verify_expansion(
+            path,
+            &output,
+            &regions,
+            session,
+            mod_resolver,
+            index,
+            &files,
+        )?;
+    }
+
+    if source != output {
+        changes.push(FileChange {
+            path: path.to_owned(),
+            old: source,
+            new: output,
+            regions,
+        });
+    }
+    for file in files {
+        // Generated files are not expanded again if they turn out to be modules of the crate
+        visited.insert(file.path.clone());
+        match changes.iter().find(|change| change.path == file.path) {
+            None => changes.push(file),
+            Some(change) if change.new == file.new => {}
+            Some(_) => {
+                return Err(SourcegenErrorKind::InvalidOutput(
+                    file.regions[0].location.clone(),
+                    format!(
+                        "file `{}` is generated by multiple sites",
+                        file.path.display()
+                    ),
+                )
+                .into())
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Expand the file once again, starting with the `output` of the first pass, and make sure nothing
+/// changes (neither the file itself, nor separate `files` generated by it).
+fn verify_expansion(
+    path: &Path,
+    output: &str,
+    regions: &[GeneratedRegion],
+    session: &mut Session,
+    mod_resolver: &ModResolver,
+    index: &ItemIndex,
+    files: &[FileChange],
+) -> Result<(), SourcegenError> {
+    let mut again_files = Vec::new();
+    let (_, mut changed) = session.silently(|session| {
+        let mut modules = Vec::new();
+        expand_source(
+            path,
+            output,
+            session,
+            mod_resolver,
+            index,
+            &mut modules,
+            &mut again_files,
+        )
+    })?;
+
+    if changed.len() == regions.len() {
+        // Same sites are visited in the same order, report them at their original locations
+        for (region, original) in changed.iter_mut().zip(regions) {
+            region.location = original.location.clone();
+        }
+    }
+    // Separate file is unchanged if both passes produce the same content or no changes at all
+    for file in &again_files {
+        if !files
+            .iter()
+            .any(|f| f.path == file.path && f.new == file.new)
+        {
+            changed.extend(file.regions.iter().cloned());
+        }
+    }
+    for file in files {
+        if !again_files.iter().any(|f| f.path == file.path) {
+            changed.extend(file.regions.iter().cloned());
+        }
+    }
+    if changed.iter().any(|region| region.changed) {
+        let sites = crate::plan::describe_changed(changed.iter());
+        return Err(SourcegenErrorKind::NotIdempotent(sites).into());
+    }
+    Ok(())
+}
+
+/// Expand all source generator invocations in the given source code. Returns the rendered output
+/// and the list of regions that were generated.
+///
+/// Out-of-line modules (`mod name;`) are not followed; instead, they are collected into `modules`
+/// along with the resolver of their parent module, so the caller can decide if they need to be
+/// processed. Changes to the separate files generated by the invocations with `output` argument are
+/// collected into `files`.
+pub(crate) fn expand_source(
+    path: &Path,
+    source: &str,
+    session: &mut Session,
+    mod_resolver: &ModResolver,
+    index: &ItemIndex,
+    modules: &mut Vec<(ModResolver, ItemMod)>,
+    files: &mut Vec<FileChange>,
+) -> Result<(String, Vec<GeneratedRegion>), SourcegenError> {
+    let mut file = syn::parse_file(source)
         .with_context(|| SourcegenErrorKind::ProcessFile(path.display().to_string()))?;
 
-    let output = if let Some(invoke) = detect_file_invocation(path, &mut file, generators)? {
+    let file_invocation = detect_file_invocation(path, &mut file, session)?;
+    let mut walker = Walker::new(path, source, session, index);
+    if let Some(invoke) = file_invocation {
         if !invoke.is_file {
             // Remove all attributes in front of the `#![sourcegen]` attribute
             file.attrs.drain(0..invoke.sourcegen_attr_index + 1);
         }
 
         // Handle full file generation
-        let context_location = invoke.context_location;
-        let result = invoke
-            .generator
-            .generate_file(invoke.args, &file)
-            .with_context(|| SourcegenErrorKind::GeneratorError(context_location))?;
-        if let Some(expansion) = result {
-            let from_loc = if invoke.is_file {
-                crate::region::item_end_span(&file.items[0]).end()
-            } else {
-                invoke.sourcegen_attr.bracket_token.span.end()
-            };
-            let from = line_column_to_offset(&source, from_loc)?;
-            let from = from + skip_whitespaces(&source[from..]);
-            let region = Region {
-                from,
-                to: source.len(),
-                indent: 0,
-            };
-
-            // Replace the whole file
-            let mut replacements = BTreeMap::new();
-            replacements.insert(region, expansion);
-            render_expansions(path, &source, &replacements, FILE_COMMENT)?
+        let from_loc = if invoke.is_file {
+            crate::region::item_end_span(&file.items[0]).end()
         } else {
-            // Nothing to replace
-            return Ok(());
-        }
+            invoke.sourcegen_attr.bracket_token.span.end()
+        };
+        let from = line_column_to_offset(source, from_loc)?;
+        let from = from + skip_whitespaces(&source[from..]);
+        let region = Region {
+            from,
+            to: source.len(),
+            indent: 0,
+        };
+        walker.handle_file_site(invoke, &file, mod_resolver, region)?;
     } else {
-        let mut replacements = BTreeMap::new();
-        handle_content(
-            path,
-            &source,
-            &mut file.items,
-            &generators,
-            &mut replacements,
-            &mod_resolver,
-        )?;
-        render_expansions(path, &source, &replacements, ITEM_COMMENT)?
-    };
+        walker.handle_content(&mut file.items, &(), mod_resolver)?;
+        walker.handle_stray_headers()?;
+    }
 
-    if source != output {
-        std::fs::write(path, output)
-            .with_context(|| SourcegenErrorKind::ProcessFile(path.display().to_string()))?;
+    let Walker {
+        mut replacements,
+        modules: mut found_modules,
+        files: mut found_files,
+        session,
+        ..
+    } = walker;
+    modules.append(&mut found_modules);
+    files.append(&mut found_files);
+    if replacements.is_empty() {
+        // Nothing to replace
+        return Ok((source.to_owned(), Vec::new()));
     }
-    Ok(())
+    let output = render_expansions(path, source, &mut replacements, session)?;
+    Ok((output, generated_regions(replacements)))
+}
+
+fn generated_regions(replacements: BTreeMap<Region, Expansion>) -> Vec<GeneratedRegion> {
+    replacements
+        .into_iter()
+        .map(|(region, expansion)| GeneratedRegion {
+            range: region.from..region.to,
+            generator: expansion.generator,
+            location: expansion.location,
+            changed: expansion.changed,
+        })
+        .collect()
 }
 
 /// Render given list of replacements into the source file. `basefile` is used to determine base
 /// directory to run `rustfmt` in (so it can use local overrides for formatting rules).
 ///
-/// `comment` is the warning comment that will be added in front of each generated block.
+/// In the keep-going mode, regions that fail to format are left unchanged and removed from the
+/// `expansions`.
 fn render_expansions(
     basefile: &Path,
     source: &str,
-    expansions: &BTreeMap<Region, TokenStream>,
-    comment: &str,
+    expansions: &mut BTreeMap<Region, Expansion>,
+    session: &mut Session,
 ) -> Result<String, SourcegenError> {
     let mut output = String::with_capacity(source.len());
     let formatter = crate::rustfmt::Formatter::new(basefile.parent().unwrap())?;
 
     let mut offset = 0;
+    let mut failed = Vec::new();
     let is_cr_lf = is_cr_lf(source);
-    for (region, tokens) in expansions {
+    for (region, expansion) in expansions.iter_mut() {
+        let tokens = &expansion.tokens;
         output += &source[offset..region.from];
         offset = region.to;
         let indent = format!("{:indent$}", "", indent = region.indent);
+        let mut rendered = String::new();
         if !tokens.is_empty() {
             let replacement = Replacement {
-                comment,
+                comment: &expansion.header,
                 is_cr_lf,
                 tokens,
             };
-            let formatted = formatter.format(basefile, replacement)?;
-            let mut first = true;
+            let started = Instant::now();
+            let result = match expansion.wrapper {
+                Some(wrapper) => formatter
+                    .format(basefile, Wrapped(wrapper, replacement))
+                    .map(|formatted| unwrap_formatted(&formatted)),
+                None => formatter.format(basefile, replacement),
+            };
+            session.report.rustfmt_finished(basefile, started.elapsed());
+            if let (Err(ref err), Some(site)) = (&result, expansion.site) {
+                session.report.site_failed(site, err);
+            }
+            let formatted = match session.recover(result)? {
+                Some(formatted) => formatted,
+                None => {
+                    output += &source[region.from..region.to];
+                    failed.push(region.clone());
+                    continue;
+                }
+            };
+            rendered += expansion.prefix;
+            let mut first = !expansion.newline;
             for line in formatted.lines() {
                 // We don't want newline on the last line (the captured region does not include the
                 // one) and also we don't want an indent on the first line (we splice after it).
@@ -140,14 +630,26 @@ fn render_expansions(
                     first = false
                 } else {
                     if is_cr_lf {
-                        output.push('\r');
+                        rendered.push('\r');
                     }
-                    output.push('\n');
-                    output += &indent;
+                    rendered.push('\n');
+                    rendered += &indent;
                 }
-                output += line;
+                rendered += line;
             }
+            if let Some(closing_indent) = expansion.closing_indent {
+                if is_cr_lf {
+                    rendered.push('\r');
+                }
+                rendered.push('\n');
+                rendered += &format!("{:indent$}", "", indent = closing_indent);
+            }
+        }
+        expansion.changed = rendered != source[region.from..region.to];
+        if let (true, Some(site)) = (expansion.changed, expansion.site) {
+            session.report.site_updated(site);
         }
+        output += &rendered;
     }
     // Insert newline at the end of the file!
     if offset == source.len() {
@@ -157,108 +659,870 @@ fn render_expansions(
         output.push('\n');
     }
     output += &source[offset..];
+
+    for region in failed {
+        expansions.remove(&region);
+    }
     Ok(output)
 }
 
-fn handle_content(
-    path: &Path,
-    source: &str,
-    items: &mut [Item],
-    generators: &GeneratorsMap,
-    replacements: &mut BTreeMap<Region, TokenStream>,
-    mod_resolver: &ModResolver,
-) -> Result<(), SourcegenError> {
-    let mut item_idx = 0;
-    while item_idx < items.len() {
-        item_idx += 1;
-        let (head, tail) = items.split_at_mut(item_idx);
-        let item = head.last_mut().unwrap();
+/// State of walking the items of a single file.
+struct Walker<'s, 'a> {
+    path: &'s Path,
+    source: &'s str,
+    session: &'s mut Session<'a>,
+    /// Index of all items of the crate, for the generators.
+    index: &'s ItemIndex,
+    replacements: BTreeMap<Region, Expansion>,
+    /// Out-of-line modules along with the resolver of their parent module.
+    modules: Vec<(ModResolver, ItemMod)>,
+    /// Byte ranges covered by the generation sites, from the `#[sourcegen]` attribute to the end of
+    /// the last generated item.
+    sites: Vec<Range<usize>>,
+    /// Separate files generated by the invocations with `output` argument.
+    files: Vec<FileChange>,
+}
 
-        let mut empty_attrs = Vec::new();
-        let attrs = crate::region::item_attributes(item).unwrap_or(&mut empty_attrs);
-        if let Some(invoke) = detect_invocation(path, attrs, generators)? {
-            // Remove all attributes in front of the `#[sourcegen]` attribute
-            attrs.drain(0..invoke.sourcegen_attr_index + 1);
-            let context_location = invoke.context_location;
-            let result = crate::region::invoke_generator(item, invoke.args, invoke.generator)
-                .with_context(|| SourcegenErrorKind::GeneratorError(context_location))?;
-            if let Some(expansion) = result {
-                let indent = invoke.sourcegen_attr.span().start().column;
-                let from_loc = invoke.sourcegen_attr.bracket_token.span.end();
-                let from = line_column_to_offset(source, from_loc)?;
-                let from = from + skip_whitespaces(&source[from..]);
-
-                // Find the first item that is not marked as "generated"
-                let skip_count = (0..tail.len())
-                    .find(|pos| {
-                        !is_generated(
-                            crate::region::item_attributes(&mut tail[*pos])
-                                .unwrap_or(&mut empty_attrs),
-                        )
-                    })
-                    .unwrap_or(tail.len());
-                let to_span = if skip_count == 0 {
-                    crate::region::item_end_span(item)
-                } else {
-                    // Skip consecutive items marked via `#[sourcegen::generated]`
-                    item_idx += skip_count;
-                    crate::region::item_end_span(&tail[skip_count - 1])
-                };
-                let to = line_column_to_offset(source, to_span.end())?;
-
-                let region = Region { from, to, indent };
-                replacements.insert(region, expansion);
+/// Outcome of handling a single item.
+enum Site {
+    /// Item is not a generation site.
+    None,
+    /// Generator returned new content for the site.
+    Expanded,
+    /// Generator returned no changes.
+    Unchanged,
+    /// Invocation is invalid or generator failed (in the keep-going mode).
+    Failed,
+}
+
+impl<'s, 'a> Walker<'s, 'a> {
+    fn new(
+        path: &'s Path,
+        source: &'s str,
+        session: &'s mut Session<'a>,
+        index: &'s ItemIndex,
+    ) -> Self {
+        Walker {
+            path,
+            source,
+            session,
+            index,
+            replacements: BTreeMap::new(),
+            modules: Vec::new(),
+            sites: Vec::new(),
+            files: Vec::new(),
+        }
+    }
+
+    /// Context of the generation sites of the module resolved by `mod_resolver`.
+    fn context(&self, mod_resolver: &ModResolver) -> GeneratorContext<'s> {
+        GeneratorContext {
+            index: self.index,
+            module_path: mod_resolver.module_path(),
+            path: self.path,
+            fs: self.session.fs,
+        }
+    }
+
+    fn handle_content<N: Node + Clone>(
+        &mut self,
+        items: &mut [N],
+        parent: &N::Parent,
+        mod_resolver: &ModResolver,
+    ) -> Result<(), SourcegenError> {
+        let mut item_idx = 0;
+        while item_idx < items.len() {
+            item_idx += 1;
+            let (head, tail) = items.split_at_mut(item_idx);
+            let item = head.last_mut().unwrap();
+
+            if is_generated(item.attributes_ref().unwrap_or_default()) {
+                // Generated items following the invocation are skipped along with it, so this one
+                // must be left behind by an invocation that was removed.
+                self.handle_orphan(item)?;
                 continue;
             }
+
+            // Consecutive items marked via `#[sourcegen::generated]` belong to the invocation
+            let generated = generated_count(tail);
+            let site = match item.as_item() {
+                Some(Item::Macro(mac)) if is_sourcegen_macro(mac) => {
+                    let mac = mac.clone();
+                    let end_span = site_end_span(item, &tail[..generated]);
+                    // Generator sees all items of the enclosing module (or function body)
+                    let items = head.iter().chain(tail.iter());
+                    let file = File {
+                        shebang: None,
+                        attrs: Vec::new(),
+                        items: items.filter_map(Node::as_item).cloned().collect(),
+                    };
+                    self.handle_macro_site::<N>(&mac, &file, mod_resolver, end_span)?
+                }
+                _ if sourcegen_attr_index(item.attributes_ref().unwrap_or_default(), false)
+                    .is_some() =>
+                {
+                    let end_span = site_end_span(item, &tail[..generated]);
+                    self.handle_site(item, parent, mod_resolver, end_span)?
+                }
+                _ => Site::None,
+            };
+            let item = head.last_mut().unwrap();
+            match site {
+                Site::None => {}
+                Site::Unchanged => item_idx += generated,
+                Site::Expanded | Site::Failed => {
+                    item_idx += generated;
+                    continue;
+                }
+            }
+
+            if let Some(item) = item.as_item_mut() {
+                self.handle_nested(item, mod_resolver)?;
+            }
+            if let Some(block) = item.block_mut() {
+                // Items declared in function bodies
+                self.handle_content(&mut block.stmts, &(), mod_resolver)?;
+            }
+            self.handle_expr_sites(item, mod_resolver)?;
         }
+        Ok(())
+    }
 
-        if let Item::Mod(item) = item {
-            let nested_mod_resolved = mod_resolver.push_module(&item.ident.to_string());
-            if item.content.is_some() {
-                let items = &mut item.content.as_mut().unwrap().1;
-                handle_content(
-                    path,
-                    source,
-                    items,
-                    generators,
-                    replacements,
-                    &nested_mod_resolved,
-                )?;
-            } else {
-                let mod_file = mod_resolver.resolve_module_file(item)?;
-                process_source_file(&mod_file, generators, &nested_mod_resolved)?;
+    /// Walk items nested in the given item: content of inline modules and items of impl blocks and
+    /// traits. Out-of-line modules are collected to be processed later.
+    fn handle_nested(
+        &mut self,
+        item: &mut Item,
+        mod_resolver: &ModResolver,
+    ) -> Result<(), SourcegenError> {
+        match item {
+            Item::Mod(item) => {
+                if item.content.is_some() {
+                    let nested_mod_resolved = mod_resolver.push_module(&item.ident.to_string());
+                    if !self.handle_inner_site(item, &nested_mod_resolved)? {
+                        let items = &mut item.content.as_mut().unwrap().1;
+                        self.handle_content(items, &(), &nested_mod_resolved)?;
+                    }
+                } else {
+                    self.modules.push((mod_resolver.clone(), item.clone()));
+                }
             }
+            Item::Impl(item) => {
+                let parent = item.clone();
+                self.handle_content(&mut item.items, &parent, mod_resolver)?;
+            }
+            Item::Trait(item) => {
+                let parent = item.clone();
+                self.handle_content(&mut item.items, &parent, mod_resolver)?;
+            }
+            _ => {}
         }
+        Ok(())
     }
-    Ok(())
-}
 
-fn is_generated(attrs: &[Attribute]) -> bool {
-    let sourcegen_attr = attrs.iter().find(|attr| {
-        attr.path
-            .segments
-            .first()
-            .map_or(false, |segment| segment.ident == "sourcegen")
-    });
-    if let Some(sourcegen) = sourcegen_attr {
-        sourcegen
-            .path
-            .segments
+    /// Handle potential `#![sourcegen]` generation site inside an inline module: everything after the
+    /// attribute up to the closing brace is generated the same way as the whole file. Returns
+    /// `false` if the module has no such attribute.
+    fn handle_inner_site(
+        &mut self,
+        item: &mut ItemMod,
+        mod_resolver: &ModResolver,
+    ) -> Result<bool, SourcegenError> {
+        let path = self.path;
+        let source = self.source;
+        let sourcegen_attr = match sourcegen_attr_index(&item.attrs, true) {
+            Some(index) => &item.attrs[index],
+            None => return Ok(false),
+        };
+        let brace = item.content.as_ref().unwrap().0.span;
+        let site_from = line_column_to_offset(source, sourcegen_attr.span().start())?;
+        let site_to = line_column_to_offset(source, brace.end())?;
+        self.sites.push(site_from..site_to);
+
+        let session = &mut *self.session;
+        let invoke = detect_invocation(path, &item.attrs, true, session).and_then(|invoke| {
+            let invoke = invoke.unwrap();
+            check_whole_file(&invoke)?;
+            Ok(invoke)
+        });
+        let invoke = match invoke {
+            Ok(invoke) => invoke,
+            Err(err) => {
+                let site = start_site(session, path, sourcegen_attr, None);
+                session.report.site_failed(site, &err);
+                session.recover::<()>(Err(err))?;
+                return Ok(true);
+            }
+        };
+
+        // Inner attributes following the `#![sourcegen]` one belong to the generated content
+        let attrs = item
+            .attrs
             .iter()
-            .skip(1)
-            .next()
-            .map_or(false, |segment| segment.ident == "generated")
+            .skip(invoke.sourcegen_attr_index + 1)
+            .filter(|attr| matches!(attr.style, AttrStyle::Inner(_)))
+            .cloned()
+            .collect();
+        let file = File {
+            shebang: None,
+            attrs,
+            items: item.content.as_ref().unwrap().1.clone(),
+        };
+
+        let indent = invoke.sourcegen_attr.span().start().column;
+        let from = line_column_to_offset(source, invoke.sourcegen_attr.bracket_token.span.end())?;
+        let from = from + skip_whitespaces(&source[from..]);
+        // Closing brace stays, along with the whitespace in front of it
+        let to = site_to - 1;
+        let to = from.max(from + source[from..to].trim_end().len());
+        let region = Region { from, to, indent };
+        self.handle_file_site(invoke, &file, mod_resolver, region)?;
+        Ok(true)
+    }
+
+    /// Generate the whole file (or the whole inline module) via `generate_file` and
+    /// `generate_mod_files`, replacing the given region.
+    fn handle_file_site(
+        &mut self,
+        invoke: GeneratorInfo,
+        file: &File,
+        mod_resolver: &ModResolver,
+        region: Region,
+    ) -> Result<(), SourcegenError> {
+        let path = self.path;
+        let context = self.context(mod_resolver);
+        let session = &mut *self.session;
+        let site = start_site(session, path, &invoke.sourcegen_attr, Some(&invoke.name));
+        let header = invocation_header(session, &invoke, true);
+        let context_location = invoke.context_location.clone();
+        let started = Instant::now();
+        let result = header.and_then(|header| {
+            let tokens = invoke
+                .generator
+                .generate_file(&context, invoke.args.clone(), file)?;
+            let mod_files =
+                invoke
+                    .generator
+                    .generate_mod_files(&context, invoke.args.clone(), file)?;
+            Ok((header, tokens, mod_files))
+        });
+        let result =
+            result.with_context(|| SourcegenErrorKind::GeneratorError(context_location.clone()));
+        session.report.site_generated(site, started.elapsed());
+        let result = match result {
+            Ok((header, tokens, Some(mod_files))) => {
+                match render_mod_files(path, mod_resolver, &invoke, mod_files, session) {
+                    Ok((names, mut mod_changes)) => {
+                        self.files.append(&mut mod_changes);
+                        match tokens {
+                            Some(tokens) => {
+                                let declarations = mod_declarations(names.iter());
+                                let tokens = quote::quote!(#tokens #declarations);
+                                Ok(Some((region, header, tokens, false)))
+                            }
+                            None => {
+                                // Content stays as-is, missing declarations go after it
+                                let declarations = missing_mod_declarations(&file.items, &names);
+                                let region = Region {
+                                    from: region.to,
+                                    ..region
+                                };
+                                if declarations.is_empty() {
+                                    Ok(None)
+                                } else {
+                                    Ok(Some((region, String::new(), declarations, true)))
+                                }
+                            }
+                        }
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+            Ok((header, tokens, None)) => Ok(tokens.map(|tokens| (region, header, tokens, false))),
+            Err(err) => Err(err),
+        };
+        if let Err(ref err) = result {
+            session.report.site_failed(site, err);
+        }
+        if let Some(Some((region, header, tokens, newline))) = session.recover(result)? {
+            session
+                .observer
+                .site_expanded(&context_location, &invoke.name);
+            let expansion = Expansion {
+                tokens,
+                header,
+                generator: invoke.name,
+                location: context_location,
+                site: Some(site),
+                wrapper: None,
+                newline,
+                closing_indent: None,
+                prefix: "",
+                changed: false,
+            };
+            self.replacements.insert(region, expansion);
+        }
+        Ok(())
+    }
+
+    /// Handle potential generation site. `end_span` is the end of the last generated item
+    /// following the site (or the end of the item itself if there are none).
+    fn handle_site<N: Node + Clone>(
+        &mut self,
+        item: &mut N,
+        parent: &N::Parent,
+        mod_resolver: &ModResolver,
+        end_span: Span,
+    ) -> Result<Site, SourcegenError> {
+        let path = self.path;
+        let source = self.source;
+        let mut empty_attrs = Vec::new();
+        let attrs = item.attributes().unwrap_or(&mut empty_attrs);
+        let sourcegen_attr = match sourcegen_attr_index(attrs, false) {
+            Some(index) => &attrs[index],
+            None => return Ok(Site::None),
+        };
+        let site_from = line_column_to_offset(source, sourcegen_attr.span().start())?;
+        let site_to = line_column_to_offset(source, end_span.end())?;
+        self.sites.push(site_from..site_to);
+
+        let context = self.context(mod_resolver);
+        let session = &mut *self.session;
+        let invoke = match detect_invocation(path, attrs, false, session) {
+            Ok(invoke) => invoke.unwrap(),
+            Err(err) => {
+                let attr = &attrs[sourcegen_attr_index(attrs, false).unwrap()];
+                let site = start_site(session, path, attr, None);
+                session.report.site_failed(site, &err);
+                session.recover::<()>(Err(err))?;
+                return Ok(Site::Failed);
+            }
+        };
+
+        let site = start_site(session, path, &invoke.sourcegen_attr, Some(&invoke.name));
+        // Remove all attributes in front of the `#[sourcegen]` attribute (and the stacked ones)
+        attrs.drain(0..invoke.sourcegen_attr_index + invoke.pipeline.len() + 1);
+        let header = invocation_header(session, &invoke, false);
+        let last_attr = invoke.last_sourcegen_attr().clone();
+        let context_location = invoke.context_location.clone();
+        let started = Instant::now();
+        let result = match header {
+            Ok(header) => invoke_pipeline(item, parent, &context, &invoke)
+                .map(|tokens| tokens.map(|tokens| (header, tokens))),
+            Err(err) => Err(err),
+        };
+        session.report.site_generated(site, started.elapsed());
+        let result = match (result, invoke.output.is_some()) {
+            (Ok(Some((header, tokens))), true) => {
+                match redirect_output(path, item.as_module_item(), &invoke, tokens, session) {
+                    Ok((tokens, file)) => {
+                        self.files.extend(file);
+                        Ok(Some((header, tokens)))
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+            (result, _) => result,
+        };
+        if let Err(ref err) = result {
+            session.report.site_failed(site, err);
+        }
+        let (header, tokens) = match session.recover(result)? {
+            Some(Some(expansion)) => expansion,
+            Some(None) => return Ok(Site::Unchanged),
+            // Generator failed, leave this item along with its generated items as-is
+            None => return Ok(Site::Failed),
+        };
+
+        session
+            .observer
+            .site_expanded(&context_location, &invoke.name);
+        let indent = invoke.sourcegen_attr.span().start().column;
+        let from_loc = last_attr.bracket_token.span.end();
+        let from = line_column_to_offset(source, from_loc)?;
+        let from = from + skip_whitespaces(&source[from..]);
+        let region = Region {
+            from,
+            to: site_to,
+            indent,
+        };
+        let expansion = Expansion {
+            tokens,
+            header,
+            generator: invoke.name,
+            location: context_location,
+            site: Some(site),
+            wrapper: N::WRAPPER,
+            newline: false,
+            closing_indent: None,
+            prefix: "",
+            changed: false,
+        };
+        self.replacements.insert(region, expansion);
+        Ok(Site::Expanded)
+    }
+
+    /// Handle `sourcegen::generate! { ... }` generation site: items following the macro up to the end
+    /// of the `#[sourcegen::generated]` chain are generated via `generate_file`. `file` is the
+    /// content of the enclosing module given to the generator for the context.
+    fn handle_macro_site<N: Node>(
+        &mut self,
+        mac: &ItemMacro,
+        file: &File,
+        mod_resolver: &ModResolver,
+        end_span: Span,
+    ) -> Result<Site, SourcegenError> {
+        let path = self.path;
+        let source = self.source;
+        let context = self.context(mod_resolver);
+        let attrs = [macro_attribute(mac)];
+        let site_from = line_column_to_offset(source, attrs[0].span().start())?;
+        let site_to = line_column_to_offset(source, end_span.end())?;
+        self.sites.push(site_from..site_to);
+
+        let session = &mut *self.session;
+        let invoke = detect_invocation(path, &attrs, false, session).and_then(|invoke| {
+            let invoke = invoke.unwrap();
+            if invoke.output.is_some() {
+                return Err(SourcegenErrorKind::InvalidOutput(
+                    invoke.context_location,
+                    "not supported for `sourcegen::generate!` invocations".to_owned(),
+                )
+                .into());
+            }
+            Ok(invoke)
+        });
+        let invoke = match invoke {
+            Ok(invoke) => invoke,
+            Err(err) => {
+                let site = start_site(session, path, &attrs[0], None);
+                session.report.site_failed(site, &err);
+                session.recover::<()>(Err(err))?;
+                return Ok(Site::Failed);
+            }
+        };
+
+        let site = start_site(session, path, &invoke.sourcegen_attr, Some(&invoke.name));
+        let header = invocation_header(session, &invoke, false);
+        let context_location = invoke.context_location.clone();
+        let started = Instant::now();
+        let result = header.and_then(|header| {
+            let tokens = invoke
+                .generator
+                .generate_file(&context, invoke.args.clone(), file)
+                .with_context(|| SourcegenErrorKind::GeneratorError(context_location.clone()))?;
+            match tokens {
+                Some(tokens) => Ok(Some((header, mark_generated::<N>(tokens, &invoke)?))),
+                None => Ok(None),
+            }
+        });
+        session.report.site_generated(site, started.elapsed());
+        if let Err(ref err) = result {
+            session.report.site_failed(site, err);
+        }
+        let (header, tokens) = match session.recover(result)? {
+            Some(Some(expansion)) => expansion,
+            Some(None) => return Ok(Site::Unchanged),
+            None => return Ok(Site::Failed),
+        };
+
+        session
+            .observer
+            .site_expanded(&context_location, &invoke.name);
+        // Macro itself stays, generated items start on the next line
+        let region = Region {
+            from: line_column_to_offset(source, invoke.sourcegen_attr.bracket_token.span.end())?,
+            to: site_to,
+            indent: invoke.sourcegen_attr.span().start().column,
+        };
+        let expansion = Expansion {
+            tokens,
+            header,
+            generator: invoke.name,
+            location: context_location,
+            site: Some(site),
+            wrapper: N::WRAPPER,
+            newline: true,
+            closing_indent: None,
+            prefix: "",
+            changed: false,
+        };
+        self.replacements.insert(region, expansion);
+        Ok(Site::Expanded)
+    }
+
+    /// Handle `sourcegen::expr!` markers in the body of the function (or method). Markers inside
+    /// items declared in the body are handled when these items are visited.
+    fn handle_expr_sites<N: Node>(
+        &mut self,
+        item: &N,
+        mod_resolver: &ModResolver,
+    ) -> Result<(), SourcegenError> {
+        let markers = item.block().map(body_expr_markers).unwrap_or_default();
+        if markers.is_empty() {
+            return Ok(());
+        }
+        let function = item.as_function().unwrap();
+        for marker in markers {
+            self.handle_expr_site(&function, marker, mod_resolver)?;
+        }
+        Ok(())
+    }
+
+    /// Handle `sourcegen::expr!(...; <expression>)` marker: the expression following the arguments
+    /// is generated via `generate_expr`, starting on the next line. The marker itself stays. Marker
+    /// without `;` (`sourcegen::expr!(...)`) is treated as the one with an empty expression.
+    fn handle_expr_site(
+        &mut self,
+        function: &ItemFn,
+        marker: ExprMarker,
+        mod_resolver: &ModResolver,
+    ) -> Result<(), SourcegenError> {
+        let path = self.path;
+        let source = self.source;
+        let context = self.context(mod_resolver);
+        let attrs = [marker.attribute()];
+        let site_from = line_column_to_offset(source, attrs[0].span().start())?;
+        let site_to = line_column_to_offset(source, marker.group.span().end())?;
+        self.sites.push(site_from..site_to);
+
+        let session = &mut *self.session;
+        let invoke = detect_invocation(path, &attrs, false, session).and_then(|invoke| {
+            let invoke = invoke.unwrap();
+            if invoke.output.is_some() {
+                return Err(SourcegenErrorKind::InvalidOutput(
+                    invoke.context_location,
+                    "not supported for `sourcegen::expr!` invocations".to_owned(),
+                )
+                .into());
+            }
+            Ok(invoke)
+        });
+        let invoke = match invoke {
+            Ok(invoke) => invoke,
+            Err(err) => {
+                let site = start_site(session, path, &attrs[0], None);
+                session.report.site_failed(site, &err);
+                session.recover::<()>(Err(err))?;
+                return Ok(());
+            }
+        };
+
+        let site = start_site(session, path, &invoke.sourcegen_attr, Some(&invoke.name));
+        let header = invocation_header(session, &invoke, false);
+        let context_location = invoke.context_location.clone();
+        let started = Instant::now();
+        let result = header.and_then(|header| {
+            let tokens = invoke
+                .generator
+                .generate_expr(&context, invoke.args.clone(), function)
+                .with_context(|| SourcegenErrorKind::GeneratorError(context_location.clone()))?;
+            Ok(tokens.map(|tokens| (header, tokens)))
+        });
+        session.report.site_generated(site, started.elapsed());
+        if let Err(ref err) = result {
+            session.report.site_failed(site, err);
+        }
+        let (header, tokens) = match session.recover(result)? {
+            Some(Some(expansion)) => expansion,
+            Some(None) | None => return Ok(()),
+        };
+
+        session
+            .observer
+            .site_expanded(&context_location, &invoke.name);
+        // Expression is indented one level deeper than the line the marker is on, with the indent
+        // step of the function body
+        let line_start = source[..site_from].rfind('\n').map_or(0, |pos| pos + 1);
+        let line_indent = skip_whitespaces(&source[line_start..site_from]);
+        let indent_step = body_indent_step(source, function);
+        let (from, prefix) = match marker.semi {
+            Some(semi) => (semi.end(), ""),
+            // Marker is not generated yet, `;` goes right after the arguments
+            None => {
+                let last = marker.args.clone().into_iter().last();
+                let args_end = last.map_or_else(|| marker.group.span_open(), |arg| arg.span());
+                (args_end.end(), ";")
+            }
+        };
+        let region = Region {
+            from: line_column_to_offset(source, from)?,
+            // Closing delimiter stays
+            to: site_to - 1,
+            indent: line_indent + indent_step,
+        };
+        let expansion = Expansion {
+            tokens,
+            header,
+            generator: invoke.name,
+            location: context_location,
+            site: Some(site),
+            wrapper: Some("fn __sourcegen()"),
+            newline: true,
+            closing_indent: Some(line_indent),
+            prefix,
+            changed: false,
+        };
+        self.replacements.insert(region, expansion);
+        Ok(())
+    }
+
+    /// Warn about generated item that does not belong to any invocation and remove it, if asked.
+    fn handle_orphan<N: Node>(&mut self, item: &N) -> Result<(), SourcegenError> {
+        let attrs = item.attributes_ref().unwrap();
+        let generated_attr = attrs.iter().find(|attr| is_generated_attr(attr)).unwrap();
+        let location = Location::from_path_span(self.path, generated_attr.span());
+        self.session.warning(&format!(
+            "{}: generated item does not follow any `#[sourcegen]` invocation",
+            location
+        ));
+
+        if self.session.remove_orphans {
+            let from = line_column_to_offset(self.source, attrs[0].span().start())?;
+            let to = line_column_to_offset(self.source, item.end_span().end())?;
+            self.remove_lines(from, to, location);
+        }
+        Ok(())
+    }
+
+    /// Warn about header comments outside of generation sites and remove them, if asked.
+    fn handle_stray_headers(&mut self) -> Result<(), SourcegenError> {
+        let templates = self.session.header_templates();
+        let literals = literal_ranges(self.source);
+        let mut offset = 0;
+        for (line_idx, line) in self.source.split_inclusive('\n').enumerate() {
+            let from = offset;
+            offset += line.len();
+            if self.sites.iter().any(|site| site.contains(&from))
+                || !templates
+                    .iter()
+                    .any(|template| crate::header::matches_header(template, line.trim()))
+            {
+                continue;
+            }
+
+            let column = line.len() - line.trim_start().len();
+            // Text inside string literals only looks like a comment
+            if literals
+                .iter()
+                .any(|range| range.contains(&(from + column)))
+            {
+                continue;
+            }
+            let location = Location::from_path_line_column(self.path, line_idx + 1, column);
+            self.session.warning(&format!(
+                "{}: generated header comment does not follow any `#[sourcegen]` invocation",
+                location
+            ));
+            if self.session.remove_orphans {
+                self.remove_lines(from + column, from + line.trim_end().len(), location);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove given range, along with the whitespaces on the lines it starts and ends.
+    fn remove_lines(&mut self, from: usize, to: usize, location: Location) {
+        let source = self.source;
+        let line_start = source[..from].rfind('\n').map_or(0, |pos| pos + 1);
+        let mut from = if source[line_start..from].trim().is_empty() {
+            line_start
+        } else {
+            from
+        };
+        let to = match source[to..].find('\n') {
+            Some(pos) if source[to..to + pos].trim().is_empty() => to + pos + 1,
+            _ => to,
+        };
+        if to == source.len() && from > 0 {
+            // Keep the newline at the end of the file, remove the one in front instead
+            from = source[..from].trim_end_matches(&['\r', '\n'][..]).len();
+        } else if source[to..].trim_start().starts_with('}') {
+            // Don't leave empty lines at the end of the block
+            let content_end = source[..from].trim_end().len();
+            from = source[content_end..from]
+                .find('\n')
+                .map_or(from, |pos| content_end + pos + 1);
+        }
+
+        let region = Region {
+            from,
+            to,
+            indent: 0,
+        };
+        let expansion = Expansion {
+            tokens: TokenStream::new(),
+            header: String::new(),
+            generator: String::new(),
+            location,
+            site: None,
+            wrapper: None,
+            newline: false,
+            closing_indent: None,
+            prefix: "",
+            changed: false,
+        };
+        self.replacements.insert(region, expansion);
+    }
+}
+
+/// Render the header comment for the invocation. Header given in the invocation itself takes
+/// precedence over the one given by the generator, which, in turn, takes precedence over the one
+/// given in the parameters.
+fn invocation_header(
+    session: &Session,
+    invoke: &GeneratorInfo,
+    is_file: bool,
+) -> Result<String, SourcegenError> {
+    // Generator producing the final output decides the header
+    let generator = invoke.pipeline.last().unwrap_or(invoke).generator;
+    let template = match invoke.header {
+        Some(ref header) => header.as_str(),
+        None if is_file => generator
+            .file_header()
+            .or(session.file_header)
+            .unwrap_or(FILE_HEADER),
+        None => generator
+            .item_header()
+            .or(session.item_header)
+            .unwrap_or(ITEM_HEADER),
+    };
+    let args = invoke
+        .pipeline
+        .iter()
+        .fold(invoke.args.clone(), |mut args, stage| {
+            args.extend(stage.args.iter().cloned());
+            args
+        });
+    render_header(template, &invoke.name, &args, &invoke.context_location)
+}
+
+/// Run the generators of the invocation one after another, each one receiving the item produced by
+/// the previous one. Items marked via `#[sourcegen::generated]` produced by any of the generators
+/// are placed after the final item, in the order of the generators.
+fn invoke_pipeline<N: Node + Clone>(
+    item: &N,
+    parent: &N::Parent,
+    context: &GeneratorContext,
+    invoke: &GeneratorInfo,
+) -> Result<Option<TokenStream>, SourcegenError> {
+    if invoke.pipeline.is_empty() {
+        return item
+            .invoke_generator(parent, context, invoke.args.clone(), invoke.generator)
+            .with_context(|| SourcegenErrorKind::GeneratorError(invoke.context_location.clone()));
+    }
+
+    let mut current = item.clone();
+    let mut generated = Vec::new();
+    let mut changed = false;
+    let stages = std::iter::once(invoke).chain(invoke.pipeline.iter());
+    for stage in stages {
+        let tokens = current
+            .invoke_generator(parent, context, stage.args.clone(), stage.generator)
+            .with_context(|| SourcegenErrorKind::GeneratorError(stage.context_location.clone()))?;
+        // Generator returning no changes passes the item to the next one as-is
+        let tokens = match tokens {
+            Some(tokens) => tokens,
+            None => continue,
+        };
+        changed = true;
+
+        let invalid_output = || {
+            SourcegenErrorKind::InvalidPipeline(
+                stage.context_location.clone(),
+                format!(
+                    "generator '{}' must produce the annotated item followed by the generated items",
+                    stage.name
+                ),
+            )
+        };
+        let mut items = N::parse_list(tokens)
+            .with_context(invalid_output)?
+            .into_iter();
+        current = match items.next() {
+            Some(item) if !is_generated(item.attributes_ref().unwrap_or_default()) => item,
+            _ => return Err(invalid_output().into()),
+        };
+        generated.extend(items);
+    }
+
+    Ok(if changed {
+        Some(quote::quote!(#current #(#generated)*))
     } else {
-        false
+        None
+    })
+}
+
+/// Mark items produced for `sourcegen::generate!` invocation via `#[sourcegen::generated]`, so
+/// they are recognized as generated ones on the next run.
+fn mark_generated<N: Node>(
+    tokens: TokenStream,
+    invoke: &GeneratorInfo,
+) -> Result<TokenStream, SourcegenError> {
+    let invalid_output = || {
+        SourcegenErrorKind::InvalidMacroOutput(invoke.context_location.clone(), invoke.name.clone())
+    };
+    let mut items = N::parse_list(tokens).with_context(invalid_output)?;
+    for item in &mut items {
+        let attrs = item.attributes().ok_or_else(invalid_output)?;
+        if !is_generated(attrs) {
+            attrs.insert(0, syn::parse_quote!(#[sourcegen::generated]));
+        }
     }
+    Ok(quote::quote!(#(#items)*))
 }
 
-fn detect_file_invocation<'a>(
+/// Register a generation site in the report.
+fn start_site(
+    session: &mut Session,
+    path: &Path,
+    sourcegen_attr: &Attribute,
+    generator: Option<&str>,
+) -> SiteId {
+    let location = Location::from_path_span(path, sourcegen_attr.span());
+    let args = sourcegen_attr.tokens.to_string();
+    let args = args.trim_start_matches('(').trim_end_matches(')').trim();
+    session
+        .report
+        .site_started(&location, generator, args.to_owned())
+}
+
+/// End of the generation site: the end of the last generated item following the annotated item
+/// (or the end of the item itself if there are none).
+fn site_end_span<N: Node>(item: &N, generated: &[N]) -> Span {
+    generated.last().unwrap_or(item).end_span()
+}
+
+/// Count consecutive items marked via `#[sourcegen::generated]` at the start of the list.
+fn generated_count<N: Node>(items: &[N]) -> usize {
+    items
+        .iter()
+        .position(|item| !is_generated(item.attributes_ref().unwrap_or_default()))
+        .unwrap_or(items.len())
+}
+
+pub(crate) fn is_generated(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(is_generated_attr)
+}
+
+/// Check if attribute is `#[sourcegen::generated]`.
+fn is_generated_attr(attr: &Attribute) -> bool {
+    let mut segments = attr.path.segments.iter();
+    segments
+        .next()
+        .is_some_and(|segment| segment.ident == "sourcegen")
+        && segments
+            .next()
+            .is_some_and(|segment| segment.ident == "generated")
+}
+
+pub(crate) fn detect_file_invocation<'a>(
     path: &Path,
     file: &mut File,
-    generators: &'a GeneratorsMap,
+    session: &Session<'a>,
 ) -> Result<Option<GeneratorInfo<'a>>, SourcegenError> {
-    if let Some(mut invoke) = detect_invocation(path, &mut file.attrs, generators)? {
+    if let Some(mut invoke) = detect_invocation(path, &file.attrs, true, session)? {
+        check_whole_file(&invoke)?;
         // This flag should only be set when we are processing a special workaround
         invoke.is_file = false;
         return Ok(Some(invoke));
@@ -269,8 +1533,10 @@ fn detect_file_invocation<'a>(
         // to `true`, we treat it as file sourcegen.
         let mut empty_attrs = Vec::new();
         let attrs = crate::region::item_attributes(item).unwrap_or(&mut empty_attrs);
-        if let Some(invoke) = detect_invocation(path, &mut attrs.clone(), generators)? {
+        if let Some(invoke) = detect_invocation(path, attrs, false, session)? {
+            // Pipelines with `file = true` are rejected while detecting the invocation
             if invoke.is_file {
+                check_whole_file(&invoke)?;
                 return Ok(Some(invoke));
             }
         }
@@ -278,72 +1544,337 @@ fn detect_file_invocation<'a>(
     Ok(None)
 }
 
-/// Collect parameters from `#[sourcegen]` attribute.
-fn detect_invocation<'a>(
+/// Whole file generation cannot be combined with other generators or redirected into another file.
+fn check_whole_file(invoke: &GeneratorInfo) -> Result<(), SourcegenError> {
+    if !invoke.pipeline.is_empty() {
+        return Err(SourcegenErrorKind::InvalidPipeline(
+            invoke.context_location.clone(),
+            "whole file generation cannot be combined with other generators".to_owned(),
+        )
+        .into());
+    }
+    if invoke.output.is_some() {
+        return Err(SourcegenErrorKind::InvalidOutput(
+            invoke.context_location.clone(),
+            "not supported for whole file generation".to_owned(),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Collect parameters from `#[sourcegen]` attribute (or from `#![sourcegen]` attribute if `inner`
+/// is set).
+pub(crate) fn detect_invocation<'a>(
     path: &Path,
     attrs: &[Attribute],
-    generators: &'a GeneratorsMap,
+    inner: bool,
+    session: &Session<'a>,
 ) -> Result<Option<GeneratorInfo<'a>>, SourcegenError> {
-    let sourcegen_attr = attrs.iter().position(|attr| {
-        attr.path
+    let attr_pos = match sourcegen_attr_index(attrs, inner) {
+        Some(attr_pos) => attr_pos,
+        None => return Ok(None),
+    };
+    let mut invoke = detect_generator(path, attrs, attr_pos, &session.generators)?;
+
+    // Adjacent `#[sourcegen]` attributes form a pipeline, top to bottom
+    let mut next = attr_pos + 1;
+    while next < attrs.len() && is_sourcegen_attr(&attrs[next], inner) {
+        let stage = detect_generator(path, attrs, next, &session.generators)?;
+        invoke.push_stage(stage)?;
+        next += 1;
+    }
+    if let Some(attr) = attrs[next..]
+        .iter()
+        .find(|attr| is_sourcegen_attr(attr, inner))
+    {
+        return Err(SourcegenErrorKind::InvalidPipeline(
+            Location::from_path_span(path, attr.span()),
+            "stacked `#[sourcegen]` attributes must not be separated by other attributes"
+                .to_owned(),
+        )
+        .into());
+    }
+    Ok(Some(invoke))
+}
+
+/// Check if macro is `sourcegen::generate!` invocation.
+pub(crate) fn is_sourcegen_macro(mac: &ItemMacro) -> bool {
+    let segments = &mac.mac.path.segments;
+    segments.len() == 2 && segments[0].ident == "sourcegen" && segments[1].ident == "generate"
+}
+
+/// Represent `sourcegen::generate! { ... }` invocation as the equivalent `#[sourcegen::generate(...)]`
+/// attribute, so it is parsed the same way as the attributes. The attribute spans the whole macro
+/// invocation (its closing bracket ends where the macro ends).
+pub(crate) fn macro_attribute(mac: &ItemMacro) -> Attribute {
+    let delimiter_span = match mac.mac.delimiter {
+        MacroDelimiter::Paren(ref paren) => paren.span,
+        MacroDelimiter::Brace(ref brace) => brace.span,
+        MacroDelimiter::Bracket(ref bracket) => bracket.span,
+    };
+    let mut args = Group::new(Delimiter::Parenthesis, mac.mac.tokens.clone());
+    args.set_span(delimiter_span);
+    Attribute {
+        pound_token: syn::token::Pound(mac.mac.path.span()),
+        style: AttrStyle::Outer,
+        bracket_token: syn::token::Bracket(crate::region::item_end_span(&Item::Macro(mac.clone()))),
+        path: mac.mac.path.clone(),
+        tokens: TokenTree::Group(args).into(),
+    }
+}
+
+/// `sourcegen::expr!(...)` marker found in a function body.
+pub(crate) struct ExprMarker {
+    /// `sourcegen::expr` path.
+    path: syn::Path,
+    /// Arguments of the marker, up to the `;`.
+    args: TokenStream,
+    /// `;` separating arguments from the generated expression (none if the marker is not generated
+    /// yet).
+    semi: Option<Span>,
+    /// Delimited content of the marker.
+    group: Group,
+}
+
+impl ExprMarker {
+    /// Represent marker as the equivalent `#[sourcegen::expr(...)]` attribute, the same way as
+    /// `macro_attribute` does.
+    pub(crate) fn attribute(&self) -> Attribute {
+        let mut args = Group::new(Delimiter::Parenthesis, self.args.clone());
+        args.set_span(self.group.span());
+        Attribute {
+            pound_token: syn::token::Pound(self.path.span()),
+            style: AttrStyle::Outer,
+            bracket_token: syn::token::Bracket(self.group.span()),
+            path: self.path.clone(),
+            tokens: TokenTree::Group(args).into(),
+        }
+    }
+}
+
+/// Find `sourcegen::expr!(...)` markers in the function body, skipping items declared in it that
+/// have bodies of their own (these are visited separately).
+pub(crate) fn body_expr_markers(block: &Block) -> Vec<ExprMarker> {
+    let mut markers = Vec::new();
+    for stmt in &block.stmts {
+        match stmt {
+            Stmt::Item(Item::Fn(_))
+            | Stmt::Item(Item::Impl(_))
+            | Stmt::Item(Item::Trait(_))
+            | Stmt::Item(Item::Mod(_)) => {}
+            stmt => find_expr_markers(stmt.to_token_stream(), &mut markers),
+        }
+    }
+    markers
+}
+
+/// Find `sourcegen::expr!(...)` markers in the tokens, including the ones nested in groups (but not
+/// in other markers).
+fn find_expr_markers(tokens: TokenStream, markers: &mut Vec<ExprMarker>) {
+    let tokens = tokens.into_iter().collect::<Vec<_>>();
+    let mut idx = 0;
+    while idx < tokens.len() {
+        if let Some(marker) = expr_marker(&tokens[idx..]) {
+            markers.push(marker);
+            idx += 6;
+            continue;
+        }
+        if let TokenTree::Group(ref group) = tokens[idx] {
+            find_expr_markers(group.stream(), markers);
+        }
+        idx += 1;
+    }
+}
+
+/// Parse `sourcegen::expr!(...)` marker at the start of the tokens.
+fn expr_marker(tokens: &[TokenTree]) -> Option<ExprMarker> {
+    let group = match tokens {
+        [TokenTree::Ident(sourcegen), TokenTree::Punct(colon1), TokenTree::Punct(colon2), TokenTree::Ident(expr), TokenTree::Punct(bang), TokenTree::Group(group), ..]
+            if sourcegen == "sourcegen"
+                && colon1.as_char() == ':'
+                && colon2.as_char() == ':'
+                && expr == "expr"
+                && bang.as_char() == '!' =>
+        {
+            group
+        }
+        _ => return None,
+    };
+    let path = syn::parse2(tokens[..4].iter().cloned().collect()).ok()?;
+    let mut args = TokenStream::new();
+    let mut semi = None;
+    for token in group.stream() {
+        match token {
+            TokenTree::Punct(ref punct) if punct.as_char() == ';' => {
+                semi = Some(punct.span());
+                break;
+            }
+            token => args.extend(std::iter::once(token)),
+        }
+    }
+    Some(ExprMarker {
+        path,
+        args,
+        semi,
+        group: group.clone(),
+    })
+}
+
+/// Find the first attribute in the `sourcegen` namespace (only inner or only outer attributes).
+pub(crate) fn sourcegen_attr_index(attrs: &[Attribute], inner: bool) -> Option<usize> {
+    attrs.iter().position(|attr| is_sourcegen_attr(attr, inner))
+}
+
+/// Check if attribute is in the `sourcegen` namespace and of the given style.
+fn is_sourcegen_attr(attr: &Attribute, inner: bool) -> bool {
+    matches!(attr.style, AttrStyle::Inner(_)) == inner
+        && attr
+            .path
             .segments
             .first()
-            .map_or(false, |segment| segment.ident == "sourcegen")
-    });
-    if let Some(attr_pos) = sourcegen_attr {
-        let invoke = detect_generator(path, attrs, attr_pos, generators)?;
-        Ok(Some(invoke))
-    } else {
-        Ok(None)
-    }
+            .is_some_and(|segment| segment.ident == "sourcegen")
 }
 
 /// Map from the line number and column back to the offset.
 fn line_column_to_offset(text: &str, lc: LineColumn) -> Result<usize, SourcegenError> {
-    let mut line = lc.line as usize;
+    let mut line = lc.line;
 
     assert_ne!(line, 0, "line number must be 1-indexed");
 
-    let mut offset = 0;
+    let mut line_start = 0;
     for (idx, ch) in text.char_indices() {
-        offset = idx;
         if line == 1 {
             break;
         }
         if ch == '\n' {
             line -= 1;
+            line_start = idx + 1;
         }
     }
-    offset += lc.column;
+    // Column counts characters, not bytes
+    let offset = text[line_start..]
+        .char_indices()
+        .nth(lc.column)
+        .map_or(text.len(), |(idx, _)| line_start + idx);
     Ok(offset.min(text.len()))
 }
 
+/// Byte ranges of all literal tokens of the source (empty if the source cannot be tokenized).
+fn literal_ranges(source: &str) -> Vec<Range<usize>> {
+    fn visit(source: &str, tokens: TokenStream, ranges: &mut Vec<Range<usize>>) {
+        for token in tokens {
+            match token {
+                TokenTree::Literal(literal) => {
+                    let span = literal.span();
+                    if let (Ok(from), Ok(to)) = (
+                        line_column_to_offset(source, span.start()),
+                        line_column_to_offset(source, span.end()),
+                    ) {
+                        ranges.push(from..to);
+                    }
+                }
+                TokenTree::Group(group) => visit(source, group.stream(), ranges),
+                _ => {}
+            }
+        }
+    }
+
+    let mut ranges = Vec::new();
+    if let Ok(tokens) = source.parse::<TokenStream>() {
+        visit(source, tokens, &mut ranges);
+    }
+    ranges
+}
+
+/// Difference between the indent of the first statement of the function body and the indent of
+/// the function itself (4 if the body is empty or on the same line as the function).
+fn body_indent_step(source: &str, function: &ItemFn) -> usize {
+    let line_indent = |line: usize| source.lines().nth(line - 1).map_or(0, skip_whitespaces);
+    let fn_line = function.sig.fn_token.span().start().line;
+    function
+        .block
+        .stmts
+        .first()
+        .map(|stmt| stmt.span().start())
+        .filter(|start| start.line != fn_line)
+        .and_then(|start| line_indent(start.line).checked_sub(line_indent(fn_line)))
+        .filter(|step| *step > 0)
+        .unwrap_or(4)
+}
+
 fn skip_whitespaces(text: &str) -> usize {
     let end = text.trim_start().as_ptr() as usize;
     let start = text.as_ptr() as usize;
     end - start
 }
 
-struct GeneratorInfo<'a> {
+pub(crate) struct GeneratorInfo<'a> {
     /// Source generator to run
-    generator: &'a dyn SourceGenerator,
-    args: AttributeArgs,
+    pub(crate) generator: &'a dyn SourceGenerator,
+    /// Name of the source generator
+    name: String,
+    pub(crate) args: AttributeArgs,
     /// `#[sourcegen]` attribute itself
     sourcegen_attr: Attribute,
     /// Index of `#[sourcegen]` attribute
     sourcegen_attr_index: usize,
     /// Location for error reporting
-    context_location: Location,
+    pub(crate) context_location: Location,
     /// If this invocation should regenerate the whole block up to the end.
     /// (this is used as a workaround for attributes not allowed on modules)
     is_file: bool,
+    /// Header template given via `header` argument (empty if header is disabled).
+    header: Option<String>,
+    /// File to write the generated code into, relative to the annotated file (given via `output`
+    /// argument).
+    output: Option<String>,
+    /// Generators stacked after this one, each receiving the output of the previous one.
+    pub(crate) pipeline: Vec<GeneratorInfo<'a>>,
+}
+
+impl<'a> GeneratorInfo<'a> {
+    /// Add generator from the next stacked attribute to the pipeline.
+    fn push_stage(&mut self, stage: GeneratorInfo<'a>) -> Result<(), SourcegenError> {
+        let invalid = |message: &str| -> SourcegenError {
+            SourcegenErrorKind::InvalidPipeline(stage.context_location.clone(), message.to_owned())
+                .into()
+        };
+        if self.is_file || stage.is_file {
+            return Err(invalid(
+                "`file = true` cannot be combined with other generators",
+            ));
+        }
+        if stage.header.is_some() {
+            if self.header.is_some() {
+                return Err(invalid("`header` can only be given once per pipeline"));
+            }
+            self.header = stage.header.clone();
+        }
+        if stage.output.is_some() {
+            if self.output.is_some() {
+                return Err(invalid("`output` can only be given once per pipeline"));
+            }
+            self.output = stage.output.clone();
+        }
+        self.name = format!("{} -> {}", self.name, stage.name);
+        self.pipeline.push(stage);
+        Ok(())
+    }
+
+    /// Last `#[sourcegen]` attribute of the pipeline; generated code starts after it.
+    fn last_sourcegen_attr(&self) -> &Attribute {
+        self.pipeline
+            .last()
+            .map_or(&self.sourcegen_attr, |stage| &stage.sourcegen_attr)
+    }
 }
 
 fn detect_generator<'a>(
     path: &Path,
     attrs: &[Attribute],
     sourcegen_attr_index: usize,
-    generators: &'a GeneratorsMap,
+    generators: &GeneratorsMap<'a>,
 ) -> Result<GeneratorInfo<'a>, SourcegenError> {
     let sourcegen_attr = attrs[sourcegen_attr_index].clone();
 
@@ -356,6 +1887,8 @@ fn detect_generator<'a>(
     if let Meta::List(list) = meta {
         let mut name: Option<&LitStr> = None;
         let mut is_file = false;
+        let mut header = None;
+        let mut output = None;
         for item in &list.nested {
             match item {
                 NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("generator") => {
@@ -375,6 +1908,41 @@ fn detect_generator<'a>(
                         is_file = value.value;
                     }
                 }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("output") => {
+                    let loc = Location::from_path_span(path, item.span());
+                    output = match nv.lit {
+                        // Generated files must stay next to the file with the invocation
+                        syn::Lit::Str(ref value)
+                            if Path::new(&value.value())
+                                .components()
+                                .any(|component| component == std::path::Component::ParentDir) =>
+                        {
+                            let message = "must not refer to the parent directory (`..`)";
+                            return Err(
+                                SourcegenErrorKind::InvalidOutput(loc, message.to_owned()).into()
+                            );
+                        }
+                        syn::Lit::Str(ref value) if Path::new(&value.value()).is_relative() => {
+                            Some(value.value())
+                        }
+                        _ => {
+                            let message = "must be a relative path (for example, `output = \"generated/widget.rs\"`)";
+                            return Err(
+                                SourcegenErrorKind::InvalidOutput(loc, message.to_owned()).into()
+                            );
+                        }
+                    };
+                }
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("header") => {
+                    header = match nv.lit {
+                        syn::Lit::Str(ref value) => Some(value.value()),
+                        syn::Lit::Bool(ref value) if !value.value => Some(String::new()),
+                        _ => {
+                            let loc = Location::from_path_span(path, item.span());
+                            return Err(SourcegenErrorKind::InvalidHeaderAttribute(loc).into());
+                        }
+                    };
+                }
                 _ => {}
             }
         }
@@ -386,16 +1954,20 @@ fn detect_generator<'a>(
             let generator = *generators.get(name.as_str()).ok_or_else(|| {
                 SourcegenErrorKind::GeneratorNotFound(
                     Location::from_path_span(path, name_span),
-                    name,
+                    name.clone(),
                 )
             })?;
             return Ok(GeneratorInfo {
                 generator,
+                name,
                 args,
                 sourcegen_attr_index,
                 sourcegen_attr,
                 context_location,
                 is_file,
+                header,
+                output,
+                pipeline: Vec::new(),
             });
         }
     }
@@ -413,6 +1985,37 @@ fn is_cr_lf(source: &str) -> bool {
     }
 }
 
+/// Replacement wrapped into an item to make it valid for formatting (for example, methods are
+/// wrapped into an impl block).
+struct Wrapped<'a>(&'static str, Replacement<'a>);
+
+impl std::fmt::Display for Wrapped<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let newline = if self.1.is_cr_lf { "\r\n" } else { "\n" };
+        write!(
+            f,
+            "{} {{{}{}{}}}{}",
+            self.0, newline, self.1, newline, newline
+        )
+    }
+}
+
+/// Remove the wrapper added by `Wrapped` from the formatted code, along with its indentation.
+fn unwrap_formatted(formatted: &str) -> String {
+    let lines = formatted.lines().collect::<Vec<_>>();
+    let inner = &lines[1..lines.len().saturating_sub(1).max(1)];
+    let indent = inner
+        .iter()
+        .find(|line| !line.trim().is_empty())
+        .map_or(0, |line| line.len() - line.trim_start().len());
+    let mut result = String::new();
+    for line in inner {
+        result += line.get(indent..).unwrap_or_else(|| line.trim_start());
+        result.push('\n');
+    }
+    result
+}
+
 /// Struct used to generate replacement code directly into stdin of `rustfmt`.
 struct Replacement<'a> {
     comment: &'a str,
@@ -424,11 +2027,13 @@ impl std::fmt::Display for Replacement<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         use std::fmt::Write;
 
-        f.write_str(self.comment)?;
-        if self.is_cr_lf {
-            f.write_char('\r')?;
+        for line in self.comment.lines() {
+            f.write_str(line)?;
+            if self.is_cr_lf {
+                f.write_char('\r')?;
+            }
+            f.write_char('\n')?;
         }
-        f.write_char('\n')?;
 
         #[cfg(feature = "disable_normalize_doc_attributes")]
         write!(f, "{}", self.tokens)?;
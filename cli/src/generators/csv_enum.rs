@@ -0,0 +1,234 @@
+//! Generator of enums from CSV/TSV tables (`csv-enum` feature).
+use super::{camel_case, doc_attrs, ident, parse_derives, read_relative, Args};
+use crate::{GeneratorContext, SourceGenerator};
+use anyhow::{anyhow, bail, Context};
+use proc_macro2::{Literal, TokenStream};
+use quote::quote;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// Derives used if the invocation does not have `derive` argument.
+const DEFAULT_DERIVES: &str = "Debug, Clone, Copy, PartialEq, Eq, Hash";
+
+/// Generate enum with a variant per row of a CSV/TSV table, followed by `as_str`, `FromStr` and
+/// `TryFrom<u32>` implementations (as `#[sourcegen::generated]` items).
+///
+/// Arguments:
+///
+/// * `table`: path to the table, relative to the annotated file. The first row must be the
+///   header with the column names, followed by at least one row.
+/// * `delimiter`: column delimiter, by default tab for `.tsv` files and comma for the others.
+/// * `name`: column with the variant names, `name` by default. Names are converted to camel case
+///   for the variants (`not_found` becomes `NotFound`), `as_str` and `FromStr` use them as-is.
+/// * `doc`: column with the doc comments of the variants, if any.
+/// * `value`: column with the discriminants of the variants, if any (the enum is `#[repr(u32)]` in
+///   this case, values must be unique). Without it, `TryFrom<u32>` uses the index of the row (starting with 0).
+/// * `derive`: comma-separated list of derives, `"Debug, Clone, Copy, PartialEq, Eq, Hash"` by
+///   default.
+pub struct CsvEnumGenerator;
+
+impl SourceGenerator for CsvEnumGenerator {
+    fn generate_item(
+        &self,
+        context: &GeneratorContext,
+        args: syn::AttributeArgs,
+        item: &syn::Item,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        let item = match item {
+            syn::Item::Enum(item) => item,
+            _ => bail!("csv-enum generator only supports enums"),
+        };
+        let args = Args::parse(
+            &args,
+            &["table", "delimiter", "name", "doc", "value", "derive"],
+        )?;
+        let (path, content) = read_relative(context, &args.required_string("table")?)?;
+        let delimiter = match args.string("delimiter")? {
+            Some(delimiter) => match delimiter.as_bytes() {
+                [delimiter] => *delimiter,
+                _ => bail!("`delimiter` must be a single character"),
+            },
+            None => default_delimiter(&path),
+        };
+        let columns = Columns {
+            name: args.string("name")?.unwrap_or_else(|| "name".to_owned()),
+            doc: args.string("doc")?,
+            value: args.string("value")?,
+        };
+        let rows = read_rows(&content, delimiter, &columns)
+            .with_context(|| format!("failed to read table `{}`", path.display()))?;
+        if rows.is_empty() {
+            bail!("table `{}` has no rows", path.display());
+        }
+        let derives = parse_derives(args.string("derive")?.as_deref().unwrap_or(DEFAULT_DERIVES))?;
+
+        let vis = &item.vis;
+        let ty = &item.ident;
+        let mut variants = Vec::with_capacity(rows.len());
+        let mut used = BTreeSet::new();
+        let mut used_values = BTreeSet::new();
+        for row in &rows {
+            let variant = ident(&camel_case(&row.name))?;
+            if !used.insert(variant.to_string()) {
+                bail!(
+                    "line {} of `{}`: duplicate variant `{}`",
+                    row.line,
+                    path.display(),
+                    variant
+                );
+            }
+            if let Some(value) = row.value {
+                if !used_values.insert(value) {
+                    bail!(
+                        "line {} of `{}`: duplicate value `{}`",
+                        row.line,
+                        path.display(),
+                        value
+                    );
+                }
+            }
+            variants.push(variant);
+        }
+        let names = rows.iter().map(|row| &row.name).collect::<Vec<_>>();
+        let values = rows
+            .iter()
+            .enumerate()
+            .map(|(index, row)| Literal::u32_unsuffixed(row.value.unwrap_or(index as u32)))
+            .collect::<Vec<_>>();
+
+        let definitions = rows.iter().zip(&variants).map(|(row, variant)| {
+            let doc = doc_attrs(row.doc.as_deref());
+            match row.value.map(Literal::u32_unsuffixed) {
+                Some(value) => quote!(#doc #variant = #value),
+                None => quote!(#doc #variant),
+            }
+        });
+        let repr = if columns.value.is_some() {
+            quote!(#[repr(u32)])
+        } else {
+            quote!()
+        };
+        let derive = if derives.is_empty() {
+            quote!()
+        } else {
+            quote!(#[derive(#(#derives),*)])
+        };
+        let name_doc = format!(
+            " Name of the variant as it is in the `{}` column.",
+            columns.name
+        );
+        let error_message = format!("unknown {}: {{}}", ty);
+        let invalid_message = format!("invalid {}: {{}}", ty);
+
+        Ok(Some(quote! {
+            #derive
+            #repr
+            #vis enum #ty {
+                #(#definitions,)*
+            }
+
+            #[sourcegen::generated]
+            impl #ty {
+                #[doc = #name_doc]
+                #vis fn as_str(&self) -> &'static str {
+                    match self {
+                        #(#ty::#variants => #names,)*
+                    }
+                }
+            }
+
+            #[sourcegen::generated]
+            impl std::str::FromStr for #ty {
+                type Err = String;
+
+                fn from_str(value: &str) -> Result<Self, Self::Err> {
+                    match value {
+                        #(#names => Ok(#ty::#variants),)*
+                        _ => Err(format!(#error_message, value)),
+                    }
+                }
+            }
+
+            #[sourcegen::generated]
+            impl std::convert::TryFrom<u32> for #ty {
+                type Error = String;
+
+                fn try_from(value: u32) -> Result<Self, Self::Error> {
+                    match value {
+                        #(#values => Ok(#ty::#variants),)*
+                        _ => Err(format!(#invalid_message, value)),
+                    }
+                }
+            }
+        }))
+    }
+}
+
+/// Names of the columns to read.
+struct Columns {
+    name: String,
+    doc: Option<String>,
+    value: Option<String>,
+}
+
+struct Row {
+    /// Line of the row in the table, for error reporting.
+    line: u64,
+    name: String,
+    doc: Option<String>,
+    value: Option<u32>,
+}
+
+fn default_delimiter(path: &Path) -> u8 {
+    if path.extension().is_some_and(|ext| ext == "tsv") {
+        b'\t'
+    } else {
+        b','
+    }
+}
+
+fn read_rows(content: &str, delimiter: u8, columns: &Columns) -> Result<Vec<Row>, anyhow::Error> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header == name)
+            .ok_or_else(|| anyhow!("column `{}` is not found", name))
+    };
+    let name_column = column(&columns.name)?;
+    let doc_column = columns.doc.as_deref().map(column).transpose()?;
+    let value_column = columns.value.as_deref().map(column).transpose()?;
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map_or(0, csv::Position::line);
+        let cell = |column: usize| record.get(column).unwrap_or_default();
+        let name = cell(name_column);
+        if name.is_empty() {
+            bail!("line {}: empty `{}` column", line, columns.name);
+        }
+        let doc = doc_column
+            .map(cell)
+            .filter(|doc| !doc.is_empty())
+            .map(str::to_owned);
+        let value = value_column
+            .map(|column| {
+                cell(column)
+                    .parse::<u32>()
+                    .map_err(|_| anyhow!("line {}: invalid value `{}`", line, cell(column)))
+            })
+            .transpose()?;
+        rows.push(Row {
+            line,
+            name: name.to_owned(),
+            doc,
+            value,
+        });
+    }
+    Ok(rows)
+}
@@ -0,0 +1,143 @@
+//! Generator embedding file contents as constants (`embed` feature).
+use super::{relative_path, Args};
+use crate::{GeneratorContext, SourceGenerator};
+use anyhow::{anyhow, bail, Context};
+use proc_macro2::{Literal, TokenStream};
+use quote::{quote, ToTokens};
+
+/// Files larger than this are rejected unless the invocation has `max_size` argument.
+const DEFAULT_MAX_SIZE: u64 = 64 * 1024;
+
+/// Generate `const` or `static` item with the content of a file as its value, so compilation does
+/// not depend on the file (unlike `include_str!` and `include_bytes!`).
+///
+/// The type of the annotated item decides how the content is embedded: `&str` (the file must be
+/// valid UTF-8) as a raw string literal, `&[u8]`, `&[u8; N]` and `[u8; N]` as a byte string
+/// literal (with `N` updated to the size of the file). The type and the attributes of the item
+/// (like doc comments and `#[cfg]`) are kept.
+///
+/// Arguments:
+///
+/// * `path`: path to the file, relative to the annotated file.
+/// * `max_size`: maximum size of the file in bytes, 64 KiB by default.
+pub struct EmbedGenerator;
+
+impl SourceGenerator for EmbedGenerator {
+    fn generate_item(
+        &self,
+        context: &GeneratorContext,
+        args: syn::AttributeArgs,
+        item: &syn::Item,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        let (kind, attrs, vis, ident, ty) = match item {
+            syn::Item::Const(item) => (
+                quote!(const),
+                &item.attrs,
+                &item.vis,
+                &item.ident,
+                &*item.ty,
+            ),
+            syn::Item::Static(item) if item.mutability.is_none() => (
+                quote!(static),
+                &item.attrs,
+                &item.vis,
+                &item.ident,
+                &*item.ty,
+            ),
+            syn::Item::Static(_) => bail!("embed generator does not support `static mut` items"),
+            _ => bail!("embed generator only supports constants and statics"),
+        };
+        let args = Args::parse(&args, &["path", "max_size"])?;
+        let path = relative_path(context, &args.required_string("path")?);
+        let max_size = args.integer("max_size")?.unwrap_or(DEFAULT_MAX_SIZE);
+
+        let content = context
+            .fs()
+            .read(&path)
+            .with_context(|| format!("failed to read `{}`", path.display()))?;
+        if content.len() as u64 > max_size {
+            bail!(
+                "`{}` is {} bytes, which is more than `max_size` of {} bytes",
+                path.display(),
+                content.len(),
+                max_size
+            );
+        }
+
+        let len = content.len();
+        let bytes = Literal::byte_string(&content);
+        let (ty, value) = match ty {
+            syn::Type::Reference(reference) => match &*reference.elem {
+                syn::Type::Path(elem) if elem.qself.is_none() && elem.path.is_ident("str") => {
+                    let text = String::from_utf8(content).map_err(|_| {
+                        anyhow!(
+                            "`{}` is not valid UTF-8, use `&[u8]` type to embed it as bytes",
+                            path.display()
+                        )
+                    })?;
+                    (ty.to_token_stream(), string_literal(&text)?)
+                }
+                syn::Type::Slice(slice) if is_u8(&slice.elem) => {
+                    (ty.to_token_stream(), quote!(#bytes))
+                }
+                syn::Type::Array(array) if is_u8(&array.elem) => {
+                    let mut reference = reference.clone();
+                    *reference.elem = syn::Type::Array(with_len(array, len));
+                    (reference.to_token_stream(), quote!(#bytes))
+                }
+                _ => bail!(unsupported_type(ty)),
+            },
+            syn::Type::Array(array) if is_u8(&array.elem) => {
+                (with_len(array, len).to_token_stream(), quote!(*#bytes))
+            }
+            _ => bail!(unsupported_type(ty)),
+        };
+        Ok(Some(quote! {
+            #(#attrs)*
+            #vis #kind #ident: #ty = #value;
+        }))
+    }
+}
+
+fn is_u8(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Path(ty) if ty.qself.is_none() && ty.path.is_ident("u8"))
+}
+
+/// Same array type with the length replaced by the size of the file.
+fn with_len(array: &syn::TypeArray, len: usize) -> syn::TypeArray {
+    let mut array = array.clone();
+    let len = Literal::usize_unsuffixed(len);
+    array.len = syn::parse_quote!(#len);
+    array
+}
+
+fn unsupported_type(ty: &syn::Type) -> String {
+    format!(
+        "embed generator only supports `&str`, `&[u8]`, `&[u8; N]` and `[u8; N]` types, got `{}`",
+        ty.to_token_stream()
+    )
+}
+
+/// Raw string literal (`r#"..."#`), so multi-line text stays readable. Text with carriage
+/// returns (not allowed in raw strings) is escaped instead.
+fn string_literal(text: &str) -> Result<TokenStream, anyhow::Error> {
+    if text.contains('\r') {
+        return Ok(Literal::string(text).into_token_stream());
+    }
+    // Enough `#` so the content cannot terminate the literal
+    let hashes = text
+        .match_indices('"')
+        .map(|(index, _)| {
+            text[index + 1..]
+                .chars()
+                .take_while(|ch| *ch == '#')
+                .count()
+                + 1
+        })
+        .max()
+        .unwrap_or(0);
+    let hashes = "#".repeat(hashes);
+    format!("r{0}\"{1}\"{0}", hashes, text)
+        .parse()
+        .map_err(|_| anyhow!("failed to create string literal"))
+}
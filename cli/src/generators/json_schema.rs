@@ -0,0 +1,666 @@
+//! Generator of data types from JSON Schema (`json-schema` feature).
+use super::Args;
+use super::{
+    camel_case, derives_serde, doc_attrs, ident, parse_derives, read_relative, snake_case,
+};
+use crate::{GeneratorContext, SourceGenerator};
+use anyhow::{anyhow, bail, Context};
+use proc_macro2::TokenStream;
+use quote::quote;
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+use syn::{Ident, Visibility};
+
+/// Derives used if the invocation does not have `derive` argument.
+const DEFAULT_DERIVES: &str = "Debug, Clone, PartialEq";
+
+/// Generate struct or enum from JSON Schema.
+///
+/// Arguments:
+///
+/// * `schema`: path to the schema file, relative to the annotated file.
+/// * `definition`: name of the definition to generate (from `definitions` or `$defs` of the
+///   schema). By default, the root schema is generated.
+/// * `derive`: comma-separated list of derives, `"Debug, Clone, PartialEq"` by default. If it
+///   includes `Serialize` or `Deserialize`, `#[serde]` attributes are generated to keep the names
+///   as they are in the schema.
+///
+/// Objects become structs with fields sorted by the property name (properties that are not
+/// `required` are wrapped in `Option`), `oneOf` becomes an enum with a variant per alternative and
+/// `enum` of strings becomes an enum with unit variants. Definitions referred to via `$ref` (and
+/// objects defined inline) are generated as separate `#[sourcegen::generated]` items following the
+/// annotated one, unless a preceding invocation in the same module already generated them. Doc
+/// comments are taken from `description`. Properties that map to the same field (for example,
+/// `fooBar` and `foo_bar`) are rejected.
+pub struct JsonSchemaGenerator;
+
+impl SourceGenerator for JsonSchemaGenerator {
+    fn generate_item(
+        &self,
+        context: &GeneratorContext,
+        args: syn::AttributeArgs,
+        item: &syn::Item,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        let (vis, ident) = match item {
+            syn::Item::Struct(item) => (&item.vis, &item.ident),
+            syn::Item::Enum(item) => (&item.vis, &item.ident),
+            _ => bail!("json-schema generator only supports structs and enums"),
+        };
+
+        // Definitions generated by the preceding invocations in the same module are only referred to
+        let mut shared = Shared::default();
+        let generator = generator_name(&args);
+        let siblings = context.index().module(context.module_path()).unwrap_or(&[]);
+        for sibling in siblings {
+            let (attrs, sibling_vis, sibling_ident) = match sibling {
+                syn::Item::Struct(item) => (&item.attrs, &item.vis, &item.ident),
+                syn::Item::Enum(item) => (&item.attrs, &item.vis, &item.ident),
+                _ => continue,
+            };
+            if sibling_ident == ident {
+                break;
+            }
+            if let Some(sibling_args) = invocation_args(attrs, generator.as_deref()) {
+                // Invalid invocations report their errors themselves
+                let _ignore = build(
+                    context,
+                    &sibling_args,
+                    sibling_vis,
+                    sibling_ident,
+                    &mut shared,
+                );
+            }
+        }
+
+        let items = build(context, &args, vis, ident, &mut shared)?;
+        Ok(Some(quote!(#(#items)*)))
+    }
+}
+
+/// Generate the type for the invocation along with all the types it refers to, except for the ones
+/// already generated by the preceding invocations (`shared`), which is updated with the generated
+/// types.
+fn build(
+    context: &GeneratorContext,
+    args: &[syn::NestedMeta],
+    vis: &Visibility,
+    ident: &Ident,
+    shared: &mut Shared,
+) -> Result<Vec<TokenStream>, anyhow::Error> {
+    let args = Args::parse(args, &["schema", "definition", "derive"])?;
+    let (path, source) = read_relative(context, &args.required_string("schema")?)?;
+    let root: Value = serde_json::from_str(&source)
+        .with_context(|| format!("invalid JSON in `{}`", path.display()))?;
+    let derives = parse_derives(args.string("derive")?.as_deref().unwrap_or(DEFAULT_DERIVES))?;
+
+    let pointer = match args.string("definition")? {
+        Some(name) => {
+            let defs = if root.get("$defs").is_some() {
+                "$defs"
+            } else {
+                "definitions"
+            };
+            format!("#/{}/{}", defs, escape_pointer(&name))
+        }
+        None => "#".to_owned(),
+    };
+    if let Some(existing) = shared.names.get(&(path.clone(), pointer.clone())) {
+        bail!(
+            "`{}` of `{}` is already generated as `{}` by another invocation in this module",
+            pointer,
+            path.display(),
+            existing
+        );
+    }
+    if shared.used.contains(&ident.to_string()) {
+        bail!(
+            "type `{}` is already generated by another invocation in this module",
+            ident
+        );
+    }
+
+    let mut builder = Builder {
+        root: &root,
+        path: &path,
+        vis,
+        serde: derives_serde(&derives),
+        derives,
+        names: BTreeMap::new(),
+        shared,
+        used: shared.used.clone(),
+        pending: VecDeque::new(),
+    };
+    builder.names.insert(pointer.clone(), ident.clone());
+    builder.used.insert(ident.to_string());
+    let schema = builder.resolve(&pointer)?;
+
+    let mut items = vec![builder.define(ident, schema, false)?];
+    while let Some((ident, schema)) = builder.pending.pop_front() {
+        items.push(builder.define(&ident, schema, true)?);
+    }
+
+    let (names, used) = (builder.names, builder.used);
+    for (pointer, ident) in names {
+        shared.names.insert((path.clone(), pointer), ident);
+    }
+    shared.used = used;
+    Ok(items)
+}
+
+/// Types generated by the invocations in the module so far.
+#[derive(Default)]
+struct Shared {
+    /// Names of the types by the schema file and the reference.
+    names: BTreeMap<(PathBuf, String), Ident>,
+    /// Names of all the types.
+    used: BTreeSet<String>,
+}
+
+/// Value of the `generator` argument of the invocation.
+fn generator_name(args: &[syn::NestedMeta]) -> Option<String> {
+    args.iter().find_map(|arg| match arg {
+        syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) if nv.path.is_ident("generator") => {
+            match nv.lit {
+                syn::Lit::Str(ref value) => Some(value.value()),
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// Arguments of the `#[sourcegen::sourcegen]` attribute invoking the given generator, if any.
+fn invocation_args(
+    attrs: &[syn::Attribute],
+    generator: Option<&str>,
+) -> Option<Vec<syn::NestedMeta>> {
+    attrs
+        .iter()
+        .filter(|attr| {
+            attr.path.segments.len() == 2
+                && attr.path.segments[0].ident == "sourcegen"
+                && attr.path.segments[1].ident == "sourcegen"
+        })
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => Some(list.nested.into_iter().collect::<Vec<_>>()),
+            _ => None,
+        })
+        .find(|args| generator.is_some() && generator_name(args).as_deref() == generator)
+}
+
+struct Builder<'a> {
+    root: &'a Value,
+    /// Schema file, to look up the types generated by other invocations.
+    path: &'a Path,
+    vis: &'a Visibility,
+    derives: Vec<syn::Path>,
+    /// Generate `#[serde]` attributes.
+    serde: bool,
+    /// Names of the types generated for the referenced definitions, by their reference.
+    names: BTreeMap<String, Ident>,
+    /// Types generated by the preceding invocations in the module.
+    shared: &'a Shared,
+    /// Names of all types generated so far.
+    used: BTreeSet<String>,
+    /// Types to generate after the current one.
+    pending: VecDeque<(Ident, &'a Value)>,
+}
+
+impl<'a> Builder<'a> {
+    /// Generate the type definition for the schema.
+    fn define(
+        &mut self,
+        ident: &Ident,
+        schema: &'a Value,
+        generated: bool,
+    ) -> Result<TokenStream, anyhow::Error> {
+        let schema = self.follow(schema)?;
+        let doc = doc_attrs(schema.get("description").and_then(Value::as_str));
+        let generated = if generated {
+            quote!(#[sourcegen::generated])
+        } else {
+            quote!()
+        };
+        let vis = self.vis;
+        let derives = &self.derives;
+        let derive = if derives.is_empty() {
+            quote!()
+        } else {
+            quote!(#[derive(#(#derives),*)])
+        };
+
+        if let Some(alternatives) = schema.get("oneOf") {
+            let alternatives = alternatives
+                .as_array()
+                .ok_or_else(|| anyhow!("`oneOf` of `{}` must be an array", ident))?;
+            let variants = self.one_of_variants(ident, alternatives)?;
+            // Alternatives that are all `const` strings are a plain enum of strings
+            let constants = alternatives
+                .iter()
+                .filter(|alternative| is_const(self.follow(alternative).ok()))
+                .count();
+            if self.serde && constants > 0 && constants < alternatives.len() {
+                bail!(
+                    "`oneOf` of `{}` mixes `const` and other alternatives, which is not supported \
+                     with serde derives",
+                    ident
+                );
+            }
+            let untagged = if self.serde && constants == 0 {
+                quote!(#[serde(untagged)])
+            } else {
+                quote!()
+            };
+            return Ok(quote! {
+                #generated
+                #doc
+                #derive
+                #untagged
+                #vis enum #ident {
+                    #(#variants,)*
+                }
+            });
+        }
+
+        if let Some(values) = schema.get("enum") {
+            let variants = self.enum_variants(ident, values)?;
+            return Ok(quote! {
+                #generated
+                #doc
+                #derive
+                #vis enum #ident {
+                    #(#variants,)*
+                }
+            });
+        }
+
+        if let Some(properties) = schema.get("properties") {
+            let properties = properties
+                .as_object()
+                .ok_or_else(|| anyhow!("`properties` of `{}` must be an object", ident))?;
+            let fields = self.fields(ident, schema, properties)?;
+            return Ok(quote! {
+                #generated
+                #doc
+                #derive
+                #vis struct #ident {
+                    #(#fields,)*
+                }
+            });
+        }
+
+        // Anything else becomes a type alias
+        let ty = self.type_of(ident, "", schema)?.into_tokens();
+        Ok(quote! {
+            #generated
+            #doc
+            #vis type #ident = #ty;
+        })
+    }
+
+    fn fields(
+        &mut self,
+        parent: &Ident,
+        schema: &'a Value,
+        properties: &'a Map<String, Value>,
+    ) -> Result<Vec<TokenStream>, anyhow::Error> {
+        let required = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|required| {
+                required
+                    .iter()
+                    .filter_map(Value::as_str)
+                    .collect::<BTreeSet<_>>()
+            })
+            .unwrap_or_default();
+        // Properties are sorted by name, so the order does not depend on `serde_json` features
+        let mut properties = properties.iter().collect::<Vec<_>>();
+        properties.sort_by_key(|&(name, _)| name);
+        let mut fields = Vec::with_capacity(properties.len());
+        let mut names = BTreeMap::new();
+        for (name, property) in properties {
+            let field = ident(&snake_case(name))?;
+            if let Some(other) = names.insert(field.to_string(), name) {
+                bail!(
+                    "`properties` of `{}` in `{}`: `{}` and `{}` both map to field `{}`",
+                    parent,
+                    self.path.display(),
+                    other,
+                    name,
+                    field
+                );
+            }
+            // Description of the property itself, falling back to the one of the referred schema
+            let description = match property.get("description") {
+                Some(description) => Some(description),
+                None => self.follow(property)?.get("description"),
+            };
+            let doc = doc_attrs(description.and_then(Value::as_str));
+            let mut ty = self.type_of(parent, name, property)?;
+            let optional = !required.contains(name.as_str());
+            let mut serde = Vec::new();
+            if self.serde && field.to_string().trim_start_matches("r#") != name {
+                serde.push(quote!(rename = #name));
+            }
+            if optional {
+                ty.optional = true;
+                if self.serde {
+                    serde.push(quote!(default, skip_serializing_if = "Option::is_none"));
+                }
+            }
+            let ty = ty.boxed();
+            let serde = if serde.is_empty() {
+                quote!()
+            } else {
+                quote!(#[serde(#(#serde),*)])
+            };
+            let vis = self.vis;
+            fields.push(quote! {
+                #doc
+                #serde
+                #vis #field: #ty
+            });
+        }
+        Ok(fields)
+    }
+
+    /// Unit variants for `enum` of strings.
+    fn enum_variants(
+        &mut self,
+        parent: &Ident,
+        values: &Value,
+    ) -> Result<Vec<TokenStream>, anyhow::Error> {
+        let values = values
+            .as_array()
+            .ok_or_else(|| anyhow!("`enum` of `{}` must be an array", parent))?;
+        let mut names = BTreeSet::new();
+        values
+            .iter()
+            .map(|value| {
+                let value = value
+                    .as_str()
+                    .ok_or_else(|| anyhow!("only `enum` of strings is supported (`{}`)", parent))?;
+                let variant = unique(&mut names, ident(&camel_case(value))?)?;
+                Ok(self.unit_variant(&variant, value, None))
+            })
+            .collect()
+    }
+
+    /// Variants for `oneOf` alternatives: unit variants for `const` strings, variants with a single
+    /// field of the alternative type for everything else.
+    fn one_of_variants(
+        &mut self,
+        parent: &Ident,
+        alternatives: &'a [Value],
+    ) -> Result<Vec<TokenStream>, anyhow::Error> {
+        let mut names = BTreeSet::new();
+        let mut variants = Vec::with_capacity(alternatives.len());
+        for alternative in alternatives {
+            let resolved = self.follow(alternative)?;
+            let description = resolved.get("description").and_then(Value::as_str);
+            if let Some(value) = resolved.get("const").and_then(Value::as_str) {
+                let variant = unique(&mut names, ident(&camel_case(value))?)?;
+                variants.push(self.unit_variant(&variant, value, description));
+                continue;
+            }
+
+            let name = variant_name(alternative, resolved);
+            let variant = unique(&mut names, ident(&camel_case(&name))?)?;
+            let ty = self.type_of(parent, &name, alternative)?.boxed();
+            let doc = doc_attrs(description);
+            variants.push(quote! {
+                #doc
+                #variant(#ty)
+            });
+        }
+        Ok(variants)
+    }
+
+    fn unit_variant(&self, variant: &Ident, value: &str, description: Option<&str>) -> TokenStream {
+        let doc = doc_attrs(description);
+        let rename = if self.serde && variant != value {
+            quote!(#[serde(rename = #value)])
+        } else {
+            quote!()
+        };
+        quote! {
+            #doc
+            #rename
+            #variant
+        }
+    }
+
+    /// Rust type for the schema. Definitions referred to via `$ref` and inline types that need a
+    /// definition of their own (objects, enums) are queued for generation.
+    fn type_of(
+        &mut self,
+        parent: &Ident,
+        name: &str,
+        schema: &'a Value,
+    ) -> Result<Type, anyhow::Error> {
+        if let Some(reference) = schema.get("$ref") {
+            let reference = reference
+                .as_str()
+                .ok_or_else(|| anyhow!("`$ref` must be a string"))?;
+            let ident = self.reference(reference)?;
+            return Ok(Type {
+                recursive: ident == *parent,
+                ..Type::new(quote!(#ident))
+            });
+        }
+        if ["properties", "oneOf", "enum"]
+            .iter()
+            .any(|key| schema.get(key).is_some())
+        {
+            let ident = self.inline(&format!("{}{}", parent, camel_case(name)), schema)?;
+            return Ok(Type::new(quote!(#ident)));
+        }
+
+        let types = match schema.get("type") {
+            Some(Value::String(ty)) => vec![ty.as_str()],
+            Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).collect(),
+            Some(_) => bail!("`type` must be a string or an array of strings"),
+            None => Vec::new(),
+        };
+        let nullable = types.contains(&"null") && types.len() > 1;
+        let types = types
+            .into_iter()
+            .filter(|ty| !nullable || *ty != "null")
+            .collect::<Vec<_>>();
+        let ty = match types.as_slice() {
+            [ty] => self.primitive(parent, name, ty, schema)?,
+            _ => quote!(serde_json::Value),
+        };
+        Ok(Type {
+            optional: nullable,
+            ..Type::new(ty)
+        })
+    }
+
+    fn primitive(
+        &mut self,
+        parent: &Ident,
+        name: &str,
+        ty: &str,
+        schema: &'a Value,
+    ) -> Result<TokenStream, anyhow::Error> {
+        let format = schema.get("format").and_then(Value::as_str);
+        Ok(match ty {
+            "string" => quote!(String),
+            "boolean" => quote!(bool),
+            "integer" => match format {
+                Some("int8") => quote!(i8),
+                Some("int16") => quote!(i16),
+                Some("int32") => quote!(i32),
+                Some("uint8") => quote!(u8),
+                Some("uint16") => quote!(u16),
+                Some("uint32") => quote!(u32),
+                Some("uint64") => quote!(u64),
+                _ => quote!(i64),
+            },
+            "number" => match format {
+                Some("float") => quote!(f32),
+                _ => quote!(f64),
+            },
+            "array" => {
+                let item = match schema.get("items") {
+                    Some(items) => self
+                        .type_of(parent, &format!("{}Item", name), items)?
+                        .into_tokens(),
+                    None => quote!(serde_json::Value),
+                };
+                quote!(Vec<#item>)
+            }
+            "object" => {
+                let value = match schema.get("additionalProperties") {
+                    Some(values @ Value::Object(object)) if !object.is_empty() => self
+                        .type_of(parent, &format!("{}Value", name), values)?
+                        .into_tokens(),
+                    _ => quote!(serde_json::Value),
+                };
+                quote!(std::collections::BTreeMap<String, #value>)
+            }
+            "null" => quote!(()),
+            ty => bail!("unsupported type `{}`", ty),
+        })
+    }
+
+    /// Name of the type for the reference, queueing the definition if it is not generated yet.
+    fn reference(&mut self, reference: &str) -> Result<Ident, anyhow::Error> {
+        if let Some(ident) = self.names.get(reference) {
+            return Ok(ident.clone());
+        }
+        let key = (self.path.to_owned(), reference.to_owned());
+        if let Some(ident) = self.shared.names.get(&key) {
+            return Ok(ident.clone());
+        }
+        let schema = self.resolve(reference)?;
+        let name = reference.rsplit('/').next().unwrap_or_default();
+        let name = unescape_pointer(name);
+        let ident = self.inline(&camel_case(&name), schema)?;
+        self.names.insert(reference.to_owned(), ident.clone());
+        Ok(ident)
+    }
+
+    /// Queue the type definition under the given name (made unique if necessary).
+    fn inline(&mut self, name: &str, schema: &'a Value) -> Result<Ident, anyhow::Error> {
+        let ident = unique(&mut self.used, ident(name)?)?;
+        self.pending.push_back((ident.clone(), schema));
+        Ok(ident)
+    }
+
+    /// Follow `$ref` of the schema (only used to look at the properties of the referred schema).
+    fn follow(&self, schema: &'a Value) -> Result<&'a Value, anyhow::Error> {
+        match schema.get("$ref").and_then(Value::as_str) {
+            Some(reference) => self.resolve(reference),
+            None => Ok(schema),
+        }
+    }
+
+    /// Resolve local reference (`#` or `#/definitions/Name`).
+    fn resolve(&self, reference: &str) -> Result<&'a Value, anyhow::Error> {
+        let pointer = reference.strip_prefix('#').ok_or_else(|| {
+            anyhow!(
+                "only local references are supported (starting with `#`), got `{}`",
+                reference
+            )
+        })?;
+        let mut schema = self.root;
+        for segment in pointer.split('/').skip(1) {
+            let segment = unescape_pointer(segment);
+            schema = match schema {
+                Value::Object(object) => object.get(&segment),
+                Value::Array(array) => segment.parse::<usize>().ok().and_then(|i| array.get(i)),
+                _ => None,
+            }
+            .ok_or_else(|| anyhow!("reference `{}` is not found in the schema", reference))?;
+        }
+        Ok(schema)
+    }
+}
+
+/// Name of the `oneOf` variant: `title` of the alternative, name of the referred definition or
+/// the type of the alternative.
+fn variant_name(alternative: &Value, resolved: &Value) -> String {
+    if let Some(title) = resolved.get("title").and_then(Value::as_str) {
+        return title.to_owned();
+    }
+    if let Some(reference) = alternative.get("$ref").and_then(Value::as_str) {
+        return unescape_pointer(reference.rsplit('/').next().unwrap_or_default());
+    }
+    match resolved.get("type").and_then(Value::as_str) {
+        Some(ty) => ty.to_owned(),
+        None => "Value".to_owned(),
+    }
+}
+
+fn is_const(schema: Option<&Value>) -> bool {
+    schema.is_some_and(|schema| schema.get("const").is_some_and(Value::is_string))
+}
+
+/// Add numeric suffix to the identifier if it is already used.
+fn unique(used: &mut BTreeSet<String>, ident: Ident) -> Result<Ident, anyhow::Error> {
+    if used.insert(ident.to_string()) {
+        return Ok(ident);
+    }
+    // Keywords are not keywords anymore with the suffix (`r#type` becomes `type2`)
+    let name = ident.to_string();
+    let name = name.trim_start_matches("r#");
+    let mut index = 2;
+    loop {
+        let candidate = super::ident(&format!("{}{}", name, index))?;
+        if used.insert(candidate.to_string()) {
+            return Ok(candidate);
+        }
+        index += 1;
+    }
+}
+
+/// Rust type for the schema, without the `Option` and `Box` wrappers.
+struct Type {
+    tokens: TokenStream,
+    /// Type is wrapped into `Option` (the property is not required or the schema allows `null`).
+    optional: bool,
+    /// Type refers to the type being defined, so it needs indirection in its fields and variants.
+    recursive: bool,
+}
+
+impl Type {
+    fn new(tokens: TokenStream) -> Self {
+        Type {
+            tokens,
+            optional: false,
+            recursive: false,
+        }
+    }
+
+    /// Type of the field or the variant: directly recursive types need indirection, so `Parent`
+    /// becomes `Box<Parent>` and `Option<Parent>` becomes `Option<Box<Parent>>`.
+    fn boxed(mut self) -> TokenStream {
+        if self.recursive {
+            let tokens = self.tokens;
+            self.tokens = quote!(Box<#tokens>);
+        }
+        self.into_tokens()
+    }
+
+    /// Type used anywhere else (items of arrays, type aliases), where no indirection is needed.
+    fn into_tokens(self) -> TokenStream {
+        let tokens = self.tokens;
+        if self.optional {
+            quote!(Option<#tokens>)
+        } else {
+            tokens
+        }
+    }
+}
+
+fn escape_pointer(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+fn unescape_pointer(segment: &str) -> String {
+    segment.replace("~1", "/").replace("~0", "~")
+}
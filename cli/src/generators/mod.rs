@@ -0,0 +1,212 @@
+//! Built-in source generators, each enabled via its own cargo feature.
+use crate::GeneratorContext;
+use anyhow::{anyhow, bail};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use syn::{Ident, Lit, Meta, NestedMeta};
+
+#[cfg(feature = "csv-enum")]
+mod csv_enum;
+#[cfg(feature = "embed")]
+mod embed;
+#[cfg(feature = "json-schema")]
+mod json_schema;
+
+#[cfg(feature = "csv-enum")]
+pub use self::csv_enum::CsvEnumGenerator;
+#[cfg(feature = "embed")]
+pub use self::embed::EmbedGenerator;
+#[cfg(feature = "json-schema")]
+pub use self::json_schema::JsonSchemaGenerator;
+
+/// Arguments handled by the tool itself rather than by generators.
+const TOOL_ARGS: &[&str] = &["generator", "file", "output", "header"];
+
+/// Literal arguments of the invocation by their name.
+struct Args {
+    values: BTreeMap<String, Lit>,
+}
+
+impl Args {
+    /// Parse arguments, rejecting the ones that are not in the `known` list.
+    fn parse(args: &[NestedMeta], known: &[&str]) -> Result<Self, anyhow::Error> {
+        let mut values = BTreeMap::new();
+        for arg in args {
+            let nv = match arg {
+                NestedMeta::Meta(Meta::NameValue(nv)) => nv,
+                _ => bail!("arguments must be in the `name = value` form"),
+            };
+            let name = nv
+                .path
+                .get_ident()
+                .map(Ident::to_string)
+                .ok_or_else(|| anyhow!("argument name must be an identifier"))?;
+            if TOOL_ARGS.contains(&name.as_str()) {
+                continue;
+            }
+            if !known.contains(&name.as_str()) {
+                bail!(
+                    "unknown argument `{}`, expected one of: {}",
+                    name,
+                    known.join(", ")
+                );
+            }
+            values.insert(name, nv.lit.clone());
+        }
+        Ok(Args { values })
+    }
+
+    fn string(&self, name: &str) -> Result<Option<String>, anyhow::Error> {
+        match self.values.get(name) {
+            None => Ok(None),
+            Some(Lit::Str(value)) => Ok(Some(value.value())),
+            Some(_) => bail!("argument `{}` must be a string", name),
+        }
+    }
+
+    fn required_string(&self, name: &str) -> Result<String, anyhow::Error> {
+        self.string(name)?
+            .ok_or_else(|| anyhow!("missing `{}` argument", name))
+    }
+
+    #[cfg(feature = "embed")]
+    fn integer(&self, name: &str) -> Result<Option<u64>, anyhow::Error> {
+        match self.values.get(name) {
+            None => Ok(None),
+            Some(Lit::Int(value)) => Ok(Some(value.base10_parse()?)),
+            Some(_) => bail!("argument `{}` must be an integer", name),
+        }
+    }
+}
+
+/// Path of the file referred to by the invocation, relative to the annotated file.
+fn relative_path(context: &GeneratorContext, path: &str) -> PathBuf {
+    context
+        .path()
+        .parent()
+        .unwrap_or_else(|| std::path::Path::new(""))
+        .join(path)
+}
+
+/// Read the file referred to by the invocation, relative to the annotated file.
+#[cfg(any(feature = "csv-enum", feature = "json-schema"))]
+fn read_relative(
+    context: &GeneratorContext,
+    path: &str,
+) -> Result<(PathBuf, String), anyhow::Error> {
+    use anyhow::Context;
+
+    let path = relative_path(context, path);
+    let content = context
+        .fs()
+        .read_to_string(&path)
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    Ok((path, content))
+}
+
+/// Parse comma-separated list of derives (for example, `"Debug, Clone"`).
+#[cfg(any(feature = "csv-enum", feature = "json-schema"))]
+fn parse_derives(derives: &str) -> Result<Vec<syn::Path>, anyhow::Error> {
+    derives
+        .split(',')
+        .map(str::trim)
+        .filter(|derive| !derive.is_empty())
+        .map(|derive| {
+            syn::parse_str::<syn::Path>(derive).map_err(|_| anyhow!("invalid derive `{}`", derive))
+        })
+        .collect()
+}
+
+/// If one of the derives is `Serialize` or `Deserialize` (so `#[serde]` attributes can be used).
+#[cfg(feature = "json-schema")]
+fn derives_serde(derives: &[syn::Path]) -> bool {
+    derives.iter().any(|derive| {
+        derive
+            .segments
+            .last()
+            .is_some_and(|last| last.ident == "Serialize" || last.ident == "Deserialize")
+    })
+}
+
+/// Doc comment attributes, one per line of the text.
+#[cfg(any(feature = "csv-enum", feature = "json-schema"))]
+fn doc_attrs(text: Option<&str>) -> proc_macro2::TokenStream {
+    let lines = text.into_iter().flat_map(str::lines).map(|line| {
+        let line = format!(" {}", line.trim_end());
+        quote::quote!(#[doc = #line])
+    });
+    quote::quote!(#(#lines)*)
+}
+
+/// Split the name into words on non-alphanumeric characters and on case changes.
+#[cfg(any(feature = "csv-enum", feature = "json-schema"))]
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev: Option<char> = None;
+    for ch in name.chars() {
+        if !ch.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev = None;
+            continue;
+        }
+        let boundary = match prev {
+            Some(prev) => ch.is_uppercase() && (prev.is_lowercase() || prev.is_numeric()),
+            None => false,
+        };
+        if boundary && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        current.push(ch);
+        prev = Some(ch);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+/// `widget_part`, `widget-part` and `widgetPart` all become `WidgetPart`.
+#[cfg(any(feature = "csv-enum", feature = "json-schema"))]
+fn camel_case(name: &str) -> String {
+    let mut result = String::new();
+    for word in words(name) {
+        let mut chars = word.chars();
+        if let Some(first) = chars.next() {
+            result.extend(first.to_uppercase());
+            result.extend(chars.flat_map(char::to_lowercase));
+        }
+    }
+    result
+}
+
+/// `WidgetPart`, `widget-part` and `widgetPart` all become `widget_part`.
+#[cfg(feature = "json-schema")]
+fn snake_case(name: &str) -> String {
+    words(name)
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Identifier for the name, escaped if it is a keyword (`r#type`) and prefixed with `_` if it
+/// does not start with a letter. Names that are not valid identifiers even then (for example,
+/// `½`) are rejected.
+#[cfg(any(feature = "csv-enum", feature = "json-schema"))]
+fn ident(name: &str) -> Result<Ident, anyhow::Error> {
+    let escaped = match name.chars().next() {
+        Some(first) if first.is_alphabetic() => name.to_owned(),
+        Some(_) => format!("_{}", name),
+        None => bail!("cannot derive an identifier from an empty name"),
+    };
+    if matches!(escaped.as_str(), "self" | "Self" | "super" | "crate") {
+        return ident(&format!("{}_", escaped));
+    }
+    // Parsed rather than created via `Ident::new`, which panics on invalid identifiers
+    syn::parse_str::<Ident>(&escaped)
+        .or_else(|_| syn::parse_str::<Ident>(&format!("r#{}", escaped)))
+        .map_err(|_| anyhow!("cannot derive an identifier from `{}`", name))
+}
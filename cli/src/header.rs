@@ -0,0 +1,133 @@
+//! Header comments added in front of the generated code.
+use crate::error::{Location, SourcegenError, SourcegenErrorKind};
+use syn::{Lit, Meta, NestedMeta};
+
+/// Default header for the blocks generated for items.
+pub static ITEM_HEADER: &str =
+    "Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.";
+/// Default header for the generated files.
+pub static FILE_HEADER: &str = "Generated. All manual edits below this line will be discarded.";
+
+/// Render header template into the comment. Each line of the template becomes a separate `//`
+/// comment line; empty template renders into no comment at all.
+///
+/// Template can refer to the generator name as `{generator}` and to any string argument of the
+/// invocation by its name (for example, `{schema}` for `schema = "widget.json"`). Use `{{` and `}}`
+/// for literal braces.
+pub fn render_header(
+    template: &str,
+    generator: &str,
+    args: &[NestedMeta],
+    location: &Location,
+) -> Result<String, SourcegenError> {
+    let mut text = String::with_capacity(template.len());
+    let mut chars = template.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                text.push('}');
+            }
+            '{' => {
+                let rest = chars.as_str();
+                let end = rest.find('}').ok_or_else(|| invalid(location, template))?;
+                let name = &rest[..end];
+                let value = if name == "generator" {
+                    generator.to_owned()
+                } else {
+                    string_arg(args, name).ok_or_else(|| invalid(location, name))?
+                };
+                text += &value;
+                chars = rest[end + 1..].chars();
+            }
+            '}' => return Err(invalid(location, template)),
+            ch => text.push(ch),
+        }
+    }
+
+    let lines = text
+        .lines()
+        .map(|line| format!("// {}", line).trim_end().to_owned())
+        .collect::<Vec<_>>();
+    Ok(lines.join("\n"))
+}
+
+/// Check if the comment line could be rendered from the given template. Placeholders match any
+/// text; lines of the template consisting only of placeholders never match.
+pub fn matches_header(template: &str, line: &str) -> bool {
+    let line = match line.strip_prefix("//") {
+        Some(line) => line.trim(),
+        None => return false,
+    };
+    template
+        .lines()
+        .any(|template_line| matches_line(template_line.trim(), line))
+}
+
+fn matches_line(template: &str, line: &str) -> bool {
+    // Split template into literal parts separated by placeholders
+    let mut parts = vec![String::new()];
+    let mut chars = template.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                parts.last_mut().unwrap().push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                parts.last_mut().unwrap().push('}');
+            }
+            '{' => {
+                let rest = chars.as_str();
+                let end = match rest.find('}') {
+                    Some(end) => end,
+                    None => return false,
+                };
+                chars = rest[end + 1..].chars();
+                parts.push(String::new());
+            }
+            ch => parts.last_mut().unwrap().push(ch),
+        }
+    }
+    if parts.iter().all(|part| part.trim().is_empty()) {
+        return false;
+    }
+
+    let (first, rest) = parts.split_first().unwrap();
+    let mut remaining = match line.strip_prefix(first.as_str()) {
+        Some(remaining) => remaining,
+        None => return false,
+    };
+    match rest.split_last() {
+        None => remaining.is_empty(),
+        Some((last, middle)) => {
+            for part in middle {
+                match remaining.find(part.as_str()) {
+                    Some(pos) => remaining = &remaining[pos + part.len()..],
+                    None => return false,
+                }
+            }
+            remaining.ends_with(last.as_str())
+        }
+    }
+}
+
+/// Find the string argument with the given name.
+fn string_arg(args: &[NestedMeta], name: &str) -> Option<String> {
+    args.iter().find_map(|arg| match arg {
+        NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident(name) => match nv.lit {
+            Lit::Str(ref value) => Some(value.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn invalid(location: &Location, what: &str) -> SourcegenError {
+    SourcegenErrorKind::InvalidHeaderTemplate(location.clone(), what.to_owned()).into()
+}
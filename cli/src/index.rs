@@ -0,0 +1,154 @@
+//! Index of the items of the crate, available to generators via [`GeneratorContext`].
+//!
+//! [`GeneratorContext`]: struct.GeneratorContext.html
+use crate::mods::ModResolver;
+use crate::vfs::FileSystem;
+use std::collections::BTreeMap;
+use std::path::Path;
+use syn::Item;
+
+/// Items of the crate by the module path (for example, `crate::a::b`), including the modules
+/// declared in other files. Items are indexed as they are in the source code before the generation
+/// (items declared in function bodies are not indexed).
+#[derive(Default)]
+pub struct ItemIndex {
+    modules: BTreeMap<String, Vec<Item>>,
+}
+
+impl ItemIndex {
+    /// Index all files of the crate, starting with the crate root. Files that cannot be read or
+    /// parsed are skipped (these are reported while generating them).
+    pub(crate) fn build(root: &Path, mod_resolver: &ModResolver, fs: &dyn FileSystem) -> Self {
+        let mut index = ItemIndex::default();
+        index.add_file(root, mod_resolver, fs);
+        index
+    }
+
+    /// Index single source file, without following out-of-line modules.
+    pub(crate) fn from_source(source: &str, mod_resolver: &ModResolver) -> Self {
+        let mut index = ItemIndex::default();
+        if let Ok(file) = syn::parse_file(source) {
+            index.add_items(file.items, mod_resolver, None);
+        }
+        index
+    }
+
+    fn add_file(&mut self, path: &Path, mod_resolver: &ModResolver, fs: &dyn FileSystem) {
+        let file = fs
+            .read_to_string(path)
+            .ok()
+            .and_then(|source| syn::parse_file(&source).ok());
+        if let Some(file) = file {
+            self.add_items(file.items, mod_resolver, Some(fs));
+        }
+    }
+
+    /// Out-of-line modules are followed if the file system is given.
+    fn add_items(
+        &mut self,
+        items: Vec<Item>,
+        mod_resolver: &ModResolver,
+        fs: Option<&dyn FileSystem>,
+    ) {
+        // Register the module even if it is empty
+        self.modules.entry(mod_resolver.module_path()).or_default();
+        for item in &items {
+            if let Item::Mod(item) = item {
+                let nested_mod_resolver = mod_resolver.push_module(&item.ident.to_string());
+                if let Some((_, ref content)) = item.content {
+                    self.add_items(content.clone(), &nested_mod_resolver, fs);
+                } else if let Some(fs) = fs {
+                    if let Ok(path) = mod_resolver.resolve_module_file(item, fs) {
+                        self.add_file(&path, &nested_mod_resolver, fs);
+                    }
+                }
+            }
+        }
+        self.modules
+            .entry(mod_resolver.module_path())
+            .or_default()
+            .extend(items);
+    }
+
+    /// Items of the module with the given path (for example, `crate::a::b`).
+    pub fn module(&self, module_path: &str) -> Option<&[Item]> {
+        self.modules.get(module_path).map(Vec::as_slice)
+    }
+
+    /// All modules of the crate along with their items, ordered by the module path.
+    pub fn modules(&self) -> impl Iterator<Item = (&str, &[Item])> {
+        self.modules
+            .iter()
+            .map(|(path, items)| (path.as_str(), items.as_slice()))
+    }
+
+    /// Find named item by its full path (for example, `crate::a::b::Widget`).
+    pub fn item(&self, path: &str) -> Option<&Item> {
+        let (module_path, name) = path.rsplit_once("::")?;
+        self.module(module_path)?
+            .iter()
+            .find(|item| item_name(item).is_some_and(|ident| ident == name))
+    }
+
+    /// All items of the crate along with the path of their module.
+    pub fn items(&self) -> impl Iterator<Item = (&str, &Item)> {
+        self.modules()
+            .flat_map(|(path, items)| items.iter().map(move |item| (path, item)))
+    }
+}
+
+/// Name of the item, if it has one.
+fn item_name(item: &Item) -> Option<&syn::Ident> {
+    match item {
+        Item::Const(item) => Some(&item.ident),
+        Item::Enum(item) => Some(&item.ident),
+        Item::ExternCrate(item) => Some(&item.ident),
+        Item::Fn(item) => Some(&item.sig.ident),
+        Item::Macro(item) => item.ident.as_ref(),
+        Item::Macro2(item) => Some(&item.ident),
+        Item::Mod(item) => Some(&item.ident),
+        Item::Static(item) => Some(&item.ident),
+        Item::Struct(item) => Some(&item.ident),
+        Item::Trait(item) => Some(&item.ident),
+        Item::TraitAlias(item) => Some(&item.ident),
+        Item::Type(item) => Some(&item.ident),
+        Item::Union(item) => Some(&item.ident),
+        _ => None,
+    }
+}
+
+/// Context of the generation site given to [`SourceGenerator::generate_item`] and the other
+/// `generate_*` methods of [`SourceGenerator`] (except for the per-item ones like
+/// `generate_struct`, which are dispatched from `generate_item`).
+///
+/// [`SourceGenerator`]: trait.SourceGenerator.html
+/// [`SourceGenerator::generate_item`]: trait.SourceGenerator.html#method.generate_item
+pub struct GeneratorContext<'a> {
+    pub(crate) index: &'a ItemIndex,
+    pub(crate) module_path: String,
+    pub(crate) path: &'a Path,
+    pub(crate) fs: &'a dyn FileSystem,
+}
+
+impl<'a> GeneratorContext<'a> {
+    /// Index of all items of the crate.
+    pub fn index(&self) -> &'a ItemIndex {
+        self.index
+    }
+
+    /// Path of the module the generation site is in (for example, `crate::a::b`).
+    pub fn module_path(&self) -> &str {
+        &self.module_path
+    }
+
+    /// File the generation site is in.
+    pub fn path(&self) -> &'a Path {
+        self.path
+    }
+
+    /// File system of the run, to read the files referred to by the invocation (for example, a
+    /// schema file next to the annotated file).
+    pub fn fs(&self) -> &'a dyn FileSystem {
+        self.fs
+    }
+}
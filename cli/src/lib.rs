@@ -4,19 +4,34 @@
 //! run and applies them to all crates that have [`sourcegen`] dependency.
 //!
 //! [`sourcegen`]: http://crates.io/crates/sourcegen
-use crate::error::{SourcegenError, SourcegenErrorKind};
+use crate::error::SourcegenError;
+use crate::observer::Observer;
+use crate::session::Session;
+use crate::vfs::FileSystem;
 use proc_macro2::TokenStream;
-use std::collections::{BTreeSet, HashMap};
-use std::path::Path;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
+mod collect;
 mod error;
 mod generate;
+#[cfg(any(feature = "csv-enum", feature = "embed", feature = "json-schema"))]
+pub mod generators;
+mod header;
+mod index;
 mod mods;
 #[cfg(not(feature = "disable_normalize_doc_attributes"))]
 mod normalize;
+pub mod observer;
+mod plan;
 mod region;
+mod report;
 mod rustfmt;
+mod session;
+#[cfg(feature = "testing")]
+pub mod testing;
 pub mod tokens;
+pub mod vfs;
 
 /// Trait to be implemented by source generators.
 pub trait SourceGenerator {
@@ -56,14 +71,157 @@ pub trait SourceGenerator {
         Ok(None)
     }
 
+    /// Generate constant. Return `None` if no changes are necessary.
+    fn generate_const(
+        &self,
+        _args: syn::AttributeArgs,
+        _item: &syn::ItemConst,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        Ok(None)
+    }
+
+    /// Generate static item. Return `None` if no changes are necessary.
+    fn generate_static(
+        &self,
+        _args: syn::AttributeArgs,
+        _item: &syn::ItemStatic,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        Ok(None)
+    }
+
+    /// Generate module item. `context` gives access to the generation site context, like the index of
+    /// all items of the crate (all of the `generate_*` methods below receive it, too). By default,
+    /// dispatches to `generate_struct`, `generate_enum`, `generate_trait`, `generate_mod`,
+    /// `generate_const` and `generate_static`. Return `None` if no changes are necessary.
+    fn generate_item(
+        &self,
+        _context: &GeneratorContext,
+        args: syn::AttributeArgs,
+        item: &syn::Item,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        match item {
+            //        ExternCrate(ItemExternCrate),
+            //        Use(ItemUse),
+            syn::Item::Static(item) => self.generate_static(args, item),
+            syn::Item::Const(item) => self.generate_const(args, item),
+            //        Fn(ItemFn),
+            syn::Item::Mod(item) => self.generate_mod(args, item),
+            //        ForeignMod(ItemForeignMod),
+            //        Type(ItemType),
+            syn::Item::Struct(item) => self.generate_struct(args, item),
+            syn::Item::Enum(item) => self.generate_enum(args, item),
+            //        Union(ItemUnion),
+            syn::Item::Trait(item) => self.generate_trait(args, item),
+            //        Impl(ItemImpl),
+            //        Macro(ItemMacro),
+            //        Macro2(ItemMacro2),
+            //        Verbatim(ItemVerbatim),
+            // FIXME: support other?
+            _ => Ok(None),
+        }
+    }
+
+    /// Generate item inside the impl block (for example, a method). `parent` is the impl block the
+    /// item is defined in. Return `None` if no changes are necessary.
+    fn generate_impl_item(
+        &self,
+        _context: &GeneratorContext,
+        _args: syn::AttributeArgs,
+        _item: &syn::ImplItem,
+        _parent: &syn::ItemImpl,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        Ok(None)
+    }
+
+    /// Generate item inside the trait definition (for example, an associated type or a method).
+    /// `parent` is the trait the item is defined in. Return `None` if no changes are necessary.
+    fn generate_trait_item(
+        &self,
+        _context: &GeneratorContext,
+        _args: syn::AttributeArgs,
+        _item: &syn::TraitItem,
+        _parent: &syn::ItemTrait,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        Ok(None)
+    }
+
+    /// Generate expression for `sourcegen::expr!(...; <expression>)` marker inside a function body.
+    /// `function` is the function the marker is in (methods are given as functions, too). Return
+    /// `None` if no changes are necessary.
+    fn generate_expr(
+        &self,
+        _context: &GeneratorContext,
+        _args: syn::AttributeArgs,
+        _function: &syn::ItemFn,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        Ok(None)
+    }
+
     /// Generate the whole file. Return `None` if no changes are necessary.
+    ///
+    /// Also used for `sourcegen::generate! { ... }` invocations: `file` is the content of the
+    /// enclosing module and the generated items are placed after the invocation (marked via
+    /// `#[sourcegen::generated]` automatically).
+    ///
+    /// Inline modules with `#![sourcegen]` inner attribute are generated via this method, too, with
+    /// `file` being the content of the module. Custom inner attributes require nightly Rust
+    /// (`#![feature(custom_inner_attributes)]`); on stable, use an outer `#[sourcegen]` attribute on
+    /// the module, which is handled by [`generate_item`].
+    ///
+    /// [`generate_item`]: #method.generate_item
     fn generate_file(
         &self,
+        _context: &GeneratorContext,
         _args: syn::AttributeArgs,
         _file: &syn::File,
     ) -> Result<Option<TokenStream>, anyhow::Error> {
         Ok(None)
     }
+
+    /// Generate new files for the module with the whole file generation (`#![sourcegen]` or the
+    /// `file = true` workaround), in addition to the content returned by `generate_file`. Paths are
+    /// relative to the directory of the nested modules (where files of `mod name;` declarations of
+    /// this module are located), for example, `widget.rs` or `widget/mod.rs`. `pub mod`
+    /// declarations for the new files are added automatically (if `generate_file` returns `None`,
+    /// the content of the module is kept and only missing declarations are added). Return `None` if
+    /// no files should be generated.
+    fn generate_mod_files(
+        &self,
+        _context: &GeneratorContext,
+        _args: syn::AttributeArgs,
+        _file: &syn::File,
+    ) -> Result<Option<Vec<(PathBuf, TokenStream)>>, anyhow::Error> {
+        Ok(None)
+    }
+
+    /// Called before the collect phase starts. The collect phase runs on every run (including every
+    /// iteration in the `fixpoint` mode), so data collected by the previous run should be cleared
+    /// here.
+    fn collect_started(&self) {}
+
+    /// Visit the generation site before any site is generated (collect phase). Called for every
+    /// site of this generator in all packages, so generators can emit aggregates (like a
+    /// dispatcher enum over all annotated handlers) while generating. Collected data needs to be
+    /// kept via interior mutability (for example, `RefCell`), see `collect_started`.
+    fn collect(&self, _site: &CollectedSite) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    /// Template of the header comment in front of the blocks generated by this generator, see
+    /// [`SourcegenParameters::item_header`]. Return `None` to use the one from the parameters.
+    ///
+    /// [`SourcegenParameters::item_header`]: struct.SourcegenParameters.html#structfield.item_header
+    fn item_header(&self) -> Option<&str> {
+        None
+    }
+
+    /// Template of the header comment in front of the files generated by this generator, see
+    /// [`SourcegenParameters::file_header`]. Return `None` to use the one from the parameters.
+    ///
+    /// [`SourcegenParameters::file_header`]: struct.SourcegenParameters.html#structfield.file_header
+    fn file_header(&self) -> Option<&str> {
+        None
+    }
 }
 
 pub(crate) type GeneratorsMap<'a> = HashMap<&'a str, &'a dyn SourceGenerator>;
@@ -80,6 +238,53 @@ pub struct SourcegenParameters<'a> {
     /// List of packages to generate code for. If not given, the default is to generate code for
     /// all of the packages.
     pub packages: BTreeSet<String>,
+    /// Keep going after errors: collect errors from all packages, files and generation sites and
+    /// report them together at the end. Sites that were generated successfully are still written.
+    pub keep_going: bool,
+    /// Write a JSON report of the run to this file: every generation site visited along with its
+    /// status, errors and time spent in generators and `rustfmt`. The report is written via `fs`,
+    /// same as the source files.
+    pub report: Option<&'a Path>,
+    /// Observer receiving progress events. If not given, messages are printed to the standard
+    /// error via [`StderrObserver`] with [`Verbosity::Normal`].
+    ///
+    /// [`StderrObserver`]: observer/struct.StderrObserver.html
+    /// [`Verbosity::Normal`]: observer/enum.Verbosity.html#variant.Normal
+    pub observer: Option<&'a dyn Observer>,
+    /// Template of the header comment added in front of each generated block. Each line of the
+    /// template becomes a `//` comment; empty template disables the comment. The template can
+    /// refer to the generator name as `{generator}` and to any string argument of the invocation
+    /// by its name (for example, `{schema}`). If not given, the default header is used.
+    ///
+    /// Generators can override it via [`SourceGenerator::item_header`], individual invocations can
+    /// override it via `header` argument (`header = "..."` or `header = false`).
+    ///
+    /// [`SourceGenerator::item_header`]: trait.SourceGenerator.html#method.item_header
+    pub item_header: Option<&'a str>,
+    /// Same as `item_header`, but for the whole generated files.
+    pub file_header: Option<&'a str>,
+    /// Remove `#[sourcegen::generated]` items and generated header comments that do not follow any
+    /// `#[sourcegen]` invocation (for example, left behind after the invocation was deleted). These
+    /// are always reported as warnings.
+    pub remove_orphans: bool,
+    /// Re-run the generation until no file changes, at most the given number of times (for
+    /// example, when generated code contains new `#[sourcegen]` invocations or generators depend
+    /// on the generated code). If files still change on the last run, sites that kept changing are
+    /// reported as `NotConverged` error. `Some(0)` is rejected as `InvalidFixpoint` error. Only
+    /// applies to [`run_sourcegen`]. Sites in the report carry the number of the iteration they
+    /// were visited on.
+    ///
+    /// [`run_sourcegen`]: fn.run_sourcegen.html
+    pub fixpoint: Option<usize>,
+    /// Verify that generators are idempotent: expand every file twice in memory and fail with
+    /// `NotIdempotent` error naming the sites if the second pass would change anything (for
+    /// example, because generator output depends on `HashMap` ordering). Nothing is written in
+    /// this case.
+    pub verify: bool,
+    /// File system to read the source files from and to write the changes (and the `report`) to.
+    /// If not given, the files on the disk are used. Note that `cargo metadata` always reads the
+    /// manifests from the disk.
+    pub fs: Option<&'a dyn FileSystem>,
 
     #[doc(hidden)]
     pub __must_use_default: (),
@@ -87,12 +292,51 @@ pub struct SourcegenParameters<'a> {
 
 /// Main entry point to the source generator toolkit.
 pub fn run_sourcegen(parameters: &SourcegenParameters) -> Result<(), SourcegenError> {
-    let generators = parameters
-        .generators
-        .iter()
-        .cloned()
-        .collect::<GeneratorsMap>();
+    let mut session = Session::new(parameters);
+    let result = run(parameters, &mut session);
+    session.finish(result)
+}
 
+fn run(parameters: &SourcegenParameters, session: &mut Session) -> Result<(), SourcegenError> {
+    let max_iterations = parameters.fixpoint.unwrap_or(1);
+    if max_iterations == 0 {
+        return Err(SourcegenErrorKind::InvalidFixpoint.into());
+    }
+    for iteration in 1.. {
+        if iteration > 1 {
+            session.report.iteration_started();
+        }
+        let changes = plan(parameters, session)?;
+        if changes.is_empty() {
+            break;
+        }
+        session.fs.write_changes(&changes, session.observer)?;
+        // Errors would be reported again on every iteration
+        if parameters.fixpoint.is_none() || session.has_errors() {
+            break;
+        }
+        if iteration >= max_iterations {
+            let sites = crate::plan::describe_changed(changes.iter().flat_map(|c| &c.regions));
+            return Err(SourcegenErrorKind::NotConverged(iteration, sites).into());
+        }
+    }
+    Ok(())
+}
+
+/// Run source generators and return the list of proposed changes, without writing any files.
+/// Use [`apply_changes`] to write them.
+///
+/// [`apply_changes`]: fn.apply_changes.html
+pub fn plan_sourcegen(parameters: &SourcegenParameters) -> Result<Vec<FileChange>, SourcegenError> {
+    let mut session = Session::new(parameters);
+    let result = plan(parameters, &mut session);
+    session.finish(result)
+}
+
+fn plan(
+    parameters: &SourcegenParameters,
+    session: &mut Session,
+) -> Result<Vec<FileChange>, SourcegenError> {
     let mut cmd = cargo_metadata::MetadataCommand::new();
     if let Some(manifest) = parameters.manifest {
         cmd.manifest_path(manifest);
@@ -120,21 +364,116 @@ pub fn run_sourcegen(parameters: &SourcegenParameters) -> Result<(), SourcegenEr
         // Only take local projects
         .filter(|p| p.source.is_none())
         // FIXME: should we look at "rename", too?
-        .filter(|p| p.dependencies.iter().any(|dep| dep.name == "sourcegen"));
+        .filter(|p| p.dependencies.iter().any(|dep| dep.name == "sourcegen"))
+        .collect::<Vec<_>>();
+
+    // Collect phase: generators see all the sites before any of them is generated
+    session.collect_started();
+    let mut collected = HashSet::new();
+    for package in &packages {
+        for target in &package.targets {
+            let parent_path = target
+                .src_path
+                .parent()
+                .ok_or(SourcegenErrorKind::MetadataError)?;
+            let mod_resolver = crate::mods::ModResolver::new(parent_path);
+            crate::collect::collect_file(&target.src_path, session, &mod_resolver, &mut collected)?;
+        }
+    }
 
+    let mut changes = Vec::new();
+    let mut visited = HashSet::new();
     for package in packages {
-        eprintln!("Generating source code in crate '{}'", package.name);
+        session.observer.package_started(&package.name);
+        session.report.package_started(&package.name);
         for target in &package.targets {
             let parent_path = target
                 .src_path
                 .parent()
                 .ok_or(SourcegenErrorKind::MetadataError)?;
             let mod_resolver = crate::mods::ModResolver::new(parent_path);
-            self::generate::process_source_file(&target.src_path, &generators, &mod_resolver)?;
+            let index = crate::index::ItemIndex::build(&target.src_path, &mod_resolver, session.fs);
+            self::generate::plan_source_file(
+                &target.src_path,
+                session,
+                &mod_resolver,
+                &index,
+                &mut visited,
+                &mut changes,
+            )?;
         }
     }
+    Ok(changes)
+}
+
+/// Expand source generator invocations in the given source code without touching any files.
+///
+/// `path` is a hint used for error reporting and for running `rustfmt` in the directory where
+/// the file is (or would be) located, so local formatting rules are picked up. Out-of-line
+/// modules (`mod name;`) are not followed and separate files (for the invocations with `output`
+/// argument and from `SourceGenerator::generate_mod_files`) are not generated. The index of items
+/// given to generators only covers the given source.
+///
+/// Headers, removal of orphans, `keep_going`, `report` and the observer from `parameters` work the
+/// same way as for [`run_sourcegen`]; `manifest`, `packages`, `fixpoint` and `verify` are ignored.
+///
+/// [`run_sourcegen`]: fn.run_sourcegen.html
+pub fn generate_source(
+    path: &Path,
+    source: &str,
+    parameters: &SourcegenParameters,
+) -> Result<String, SourcegenError> {
+    let mut session = Session::new(parameters);
+    let mod_resolver =
+        crate::mods::ModResolver::new(path.parent().unwrap_or_else(|| Path::new("")));
+    let index = crate::index::ItemIndex::from_source(source, &mod_resolver);
+    session.collect_started();
+    let result = crate::collect::collect_source(path, source, &mut session, &mod_resolver, None)
+        .and_then(|()| {
+            crate::generate::expand_source(
+                path,
+                source,
+                &mut session,
+                &mod_resolver,
+                &index,
+                &mut Vec::new(),
+                &mut Vec::new(),
+            )
+        });
+    let (output, _regions) = session.finish(result)?;
+    Ok(output)
+}
+
+/// Filter mode: read source code from the standard input, expand source generator invocations
+/// in it and write the result to the standard output. `path` is a hint, same as for
+/// [`generate_source`].
+///
+/// This crate is a library, so it is up to the tool built on top of it to decide when to run in
+/// filter mode (for example, when invoked with `--stdin`) instead of [`run_sourcegen`].
+///
+/// [`run_sourcegen`]: fn.run_sourcegen.html
+///
+/// [`generate_source`]: fn.generate_source.html
+pub fn run_sourcegen_filter(
+    parameters: &SourcegenParameters,
+    path: &Path,
+) -> Result<(), SourcegenError> {
+    use std::io::{Read, Write};
+
+    let mut source = String::new();
+    std::io::stdin()
+        .read_to_string(&mut source)
+        .with_context(|| SourcegenErrorKind::ProcessFile(path.display().to_string()))?;
+    let output = generate_source(path, &source, parameters)?;
+    std::io::stdout()
+        .write_all(output.as_bytes())
+        .with_context(|| SourcegenErrorKind::ProcessFile(path.display().to_string()))?;
     Ok(())
 }
 
-pub use crate::generate::process_single_file;
+pub use crate::collect::{CollectedSite, SiteItem};
+pub use crate::error::{Location, SourcegenErrorKind, SourcegenErrors};
+pub use crate::generate::{process_single_file, process_single_file_with, verify_single_file};
+pub use crate::index::{GeneratorContext, ItemIndex};
+pub use crate::plan::{apply_changes, apply_changes_with, FileChange, GeneratedRegion};
 use anyhow::Context;
@@ -1,37 +1,61 @@
 use crate::error::{SourcegenError, SourcegenErrorKind};
-use std::path::PathBuf;
+use crate::vfs::FileSystem;
+use std::path::{Path, PathBuf};
 use syn::{Attribute, ItemMod, Lit, Meta};
 
 // FIXME: support cfg_attr, too?
+#[derive(Clone)]
 pub struct ModResolver {
     base: PathBuf,
+    /// Path of the module, starting with `crate`.
+    module_path: Vec<String>,
 }
 
 impl ModResolver {
     pub fn new(base: impl Into<PathBuf>) -> Self {
-        ModResolver { base: base.into() }
+        ModResolver {
+            base: base.into(),
+            module_path: vec!["crate".to_owned()],
+        }
     }
 
     /// Nested module -- append a new directory.
     pub fn push_module(&self, name: &str) -> Self {
+        let mut module_path = self.module_path.clone();
+        module_path.push(name.trim_start_matches("r#").to_owned());
         ModResolver {
             base: self.base.join(name),
+            module_path,
         }
     }
 
+    /// Directory where files of the nested modules are located.
+    pub fn module_dir(&self) -> &Path {
+        &self.base
+    }
+
+    /// Path of the module (for example, `crate::a::b`).
+    pub fn module_path(&self) -> String {
+        self.module_path.join("::")
+    }
+
     /// Resolve to a module file.
-    pub fn resolve_module_file(&self, item: &ItemMod) -> Result<PathBuf, SourcegenError> {
+    pub fn resolve_module_file(
+        &self,
+        item: &ItemMod,
+        fs: &dyn FileSystem,
+    ) -> Result<PathBuf, SourcegenError> {
         if let Some(path) = detect_mod_path(&item.attrs) {
             Ok(self.base.join(path))
         } else {
             let name = item.ident.to_string();
             let name = name.trim_start_matches("r#");
-            let path = self.base.join(&format!("{}.rs", name));
-            if path.is_file() {
+            let path = self.base.join(format!("{}.rs", name));
+            if fs.is_file(&path) {
                 return Ok(path);
             }
-            let path = self.base.join(&name).join("mod.rs");
-            if path.is_file() {
+            let path = self.base.join(name).join("mod.rs");
+            if fs.is_file(&path) {
                 return Ok(path);
             }
             Err(SourcegenErrorKind::CannotResolveModule(
@@ -79,19 +79,17 @@ fn as_doc_comment(first: &TokenTree, second: &TokenTree) -> Option<String> {
             if first.as_char() == '#' && group.delimiter() == Delimiter::Bracket =>
         {
             let mut it = group.stream().into_iter();
-            match (it.next(), it.next(), it.next()) {
-                (
-                    Some(TokenTree::Ident(ident)),
-                    Some(TokenTree::Punct(punct)),
-                    Some(TokenTree::Literal(lit)),
-                ) => {
-                    if ident == "doc" && punct.as_char() == '=' {
-                        if let Lit::Str(lit) = Lit::new(lit) {
-                            return Some(lit.value());
-                        }
+            if let (
+                Some(TokenTree::Ident(ident)),
+                Some(TokenTree::Punct(punct)),
+                Some(TokenTree::Literal(lit)),
+            ) = (it.next(), it.next(), it.next())
+            {
+                if ident == "doc" && punct.as_char() == '=' {
+                    if let Lit::Str(lit) = Lit::new(lit) {
+                        return Some(lit.value());
                     }
                 }
-                _ => {}
             }
         }
         _ => {}
@@ -0,0 +1,95 @@
+//! Observing the progress of source generation.
+use crate::error::{Location, SourcegenError};
+use std::path::Path;
+
+/// Receives events as source generation progresses. All methods do nothing by default.
+pub trait Observer {
+    /// Started generating source code in the package.
+    fn package_started(&self, _name: &str) {}
+
+    /// Started processing the source file.
+    fn file_started(&self, _path: &Path) {}
+
+    /// Generator produced new content for the site at the given location.
+    fn site_expanded(&self, _location: &Location, _generator: &str) {}
+
+    /// File was written to the disk.
+    fn file_written(&self, _path: &Path) {}
+
+    /// Something looks wrong, but source generation can continue.
+    fn warning(&self, _message: &str) {}
+
+    /// Error that was recovered from in the keep-going mode. These errors are also returned at the
+    /// end of the run.
+    fn error(&self, _error: &SourcegenError) {}
+}
+
+/// How much [`StderrObserver`] prints.
+///
+/// [`StderrObserver`]: struct.StderrObserver.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Only warnings.
+    Quiet,
+    /// Warnings and packages being processed.
+    Normal,
+    /// Everything.
+    Verbose,
+}
+
+/// Observer printing messages to the standard error. This is the default observer.
+#[derive(Debug, Clone, Copy)]
+pub struct StderrObserver {
+    verbosity: Verbosity,
+}
+
+impl StderrObserver {
+    pub const fn new(verbosity: Verbosity) -> Self {
+        StderrObserver { verbosity }
+    }
+}
+
+impl Default for StderrObserver {
+    fn default() -> Self {
+        StderrObserver::new(Verbosity::Normal)
+    }
+}
+
+impl Observer for StderrObserver {
+    fn package_started(&self, name: &str) {
+        if self.verbosity >= Verbosity::Normal {
+            eprintln!("Generating source code in crate '{}'", name);
+        }
+    }
+
+    fn file_started(&self, path: &Path) {
+        if self.verbosity >= Verbosity::Verbose {
+            eprintln!("Processing '{}'", path.display());
+        }
+    }
+
+    fn site_expanded(&self, location: &Location, generator: &str) {
+        if self.verbosity >= Verbosity::Verbose {
+            eprintln!("{}: expanded generator '{}'", location, generator);
+        }
+    }
+
+    fn file_written(&self, path: &Path) {
+        if self.verbosity >= Verbosity::Verbose {
+            eprintln!("Updated '{}'", path.display());
+        }
+    }
+
+    fn warning(&self, message: &str) {
+        eprintln!("Warning: {}", message);
+    }
+
+    fn error(&self, error: &SourcegenError) {
+        if self.verbosity >= Verbosity::Verbose {
+            eprintln!("Error: {:#}", error);
+        }
+    }
+}
+
+/// Observer used when none is given in the parameters.
+pub(crate) static DEFAULT_OBSERVER: StderrObserver = StderrObserver::new(Verbosity::Normal);
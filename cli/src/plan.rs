@@ -0,0 +1,196 @@
+//! Changes proposed by the source generator, before they are written to the disk.
+use crate::error::{Location, SourcegenError, SourcegenErrorKind};
+use crate::observer::{Observer, DEFAULT_OBSERVER};
+use crate::vfs::{FileSystem, REAL_FILE_SYSTEM};
+use anyhow::Context;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// Suffix of the backup files created while changes are written.
+const BACKUP_SUFFIX: &str = ".sourcegen-backup";
+
+/// Proposed change to a single file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// Path to the file to change.
+    pub path: PathBuf,
+    /// Current content of the file (empty if the file does not exist yet).
+    pub old: String,
+    /// New content of the file.
+    pub new: String,
+    /// Regions of the current content that are replaced by generated code.
+    pub regions: Vec<GeneratedRegion>,
+}
+
+/// Region of the file that is replaced by the source generator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedRegion {
+    /// Byte range of the region in the current content of the file.
+    pub range: Range<usize>,
+    /// Name of the generator that generated this region.
+    pub generator: String,
+    /// Location of the `#[sourcegen]` invocation.
+    pub location: Location,
+    /// If the generated code differs from the current content of the region.
+    pub changed: bool,
+}
+
+/// Describe generation sites of the changed regions for error messages.
+pub(crate) fn describe_changed<'r>(regions: impl Iterator<Item = &'r GeneratedRegion>) -> String {
+    regions
+        .filter(|region| region.changed)
+        .map(|region| format!("{} ({})", region.location, region.generator))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Write all changes to the disk, all or nothing.
+///
+/// New content is first written to temporary files next to the target files, then each target
+/// file is backed up (as `<name>.sourcegen-backup`) and replaced by renaming the temporary file
+/// over it. If any of the steps fail, files that were already replaced are restored from their
+/// backups. Backups are removed once all files are written; if the process is interrupted in the
+/// middle of the write phase, the original content can be recovered from the backup files that
+/// are left behind.
+pub fn apply_changes(changes: &[FileChange]) -> Result<(), SourcegenError> {
+    apply_changes_with(&REAL_FILE_SYSTEM, changes)
+}
+
+/// Same as [`apply_changes`], but writes the changes via the given file system (for example, the
+/// one the changes were planned with, see `SourcegenParameters::fs`).
+///
+/// [`apply_changes`]: fn.apply_changes.html
+pub fn apply_changes_with(
+    fs: &dyn FileSystem,
+    changes: &[FileChange],
+) -> Result<(), SourcegenError> {
+    fs.write_changes(changes, &DEFAULT_OBSERVER)
+}
+
+/// Atomic write of the changes to the disk, used by `RealFileSystem` (see [`apply_changes`]).
+///
+/// [`apply_changes`]: fn.apply_changes.html
+pub(crate) fn write_changes(
+    changes: &[FileChange],
+    observer: &dyn Observer,
+) -> Result<(), SourcegenError> {
+    // Make sure nothing changed since the changes were planned and we are not going to overwrite
+    // backups left behind by an interrupted run.
+    for change in changes {
+        let current = if is_new_file(change) {
+            String::new()
+        } else {
+            std::fs::read_to_string(&change.path)
+                .with_context(|| process_file_error(&change.path))?
+        };
+        if current != change.old {
+            return Err(SourcegenErrorKind::FileModified(change.path.display().to_string()).into());
+        }
+        let backup = backup_path(&change.path);
+        if backup.exists() {
+            return Err(SourcegenErrorKind::BackupExists(backup.display().to_string()).into());
+        }
+    }
+
+    // Write new content to the temporary files first; these are removed automatically on failure.
+    let mut staged = Vec::with_capacity(changes.len());
+    for change in changes {
+        staged.push(stage_change(change)?);
+    }
+
+    let mut written = Vec::with_capacity(changes.len());
+    for (change, temp) in changes.iter().zip(staged) {
+        let created = is_new_file(change);
+        if let Err(err) = replace_file(&change.path, temp, created) {
+            rollback(&written, observer);
+            return Err(err);
+        }
+        written.push((change.path.as_path(), created));
+    }
+
+    for (path, _) in written {
+        // Ignore the errors: all the files are written at this point
+        let _ignore = std::fs::remove_file(backup_path(path));
+        observer.file_written(path);
+    }
+    Ok(())
+}
+
+/// Write new content into a temporary file in the same directory as the target file (so it can
+/// be atomically renamed later on).
+fn stage_change(change: &FileChange) -> Result<NamedTempFile, SourcegenError> {
+    let path = &change.path;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if is_new_file(change) {
+        std::fs::create_dir_all(dir).with_context(|| process_file_error(path))?;
+    }
+    let temp = NamedTempFile::new_in(dir).with_context(|| process_file_error(path))?;
+    std::fs::write(temp.path(), &change.new).with_context(|| process_file_error(path))?;
+    if !is_new_file(change) {
+        let permissions = std::fs::metadata(path)
+            .with_context(|| process_file_error(path))?
+            .permissions();
+        std::fs::set_permissions(temp.path(), permissions)
+            .with_context(|| process_file_error(path))?;
+    }
+    Ok(temp)
+}
+
+/// Backup the target file (unless it is created) and move staged content over it.
+fn replace_file(path: &Path, temp: NamedTempFile, created: bool) -> Result<(), SourcegenError> {
+    if !created {
+        std::fs::copy(path, backup_path(path)).with_context(|| process_file_error(path))?;
+    }
+    if let Err(err) = temp.persist(path) {
+        // Target file is not replaced, so its backup is not needed (and would fail the next run)
+        if !created {
+            let _ignore = std::fs::remove_file(backup_path(path));
+        }
+        return Err(err.error).with_context(|| process_file_error(path));
+    }
+    Ok(())
+}
+
+/// Restore all written files from their backups; remove the ones that were created.
+fn rollback(written: &[(&Path, bool)], observer: &dyn Observer) {
+    for &(path, created) in written {
+        if created {
+            if let Err(err) = std::fs::remove_file(path) {
+                observer.warning(&format!(
+                    "failed to remove created file '{}': {}",
+                    path.display(),
+                    err
+                ));
+            }
+            continue;
+        }
+        let backup = backup_path(path);
+        if let Err(err) = std::fs::rename(&backup, path) {
+            observer.warning(&format!(
+                "failed to restore '{}' from backup '{}': {}",
+                path.display(),
+                backup.display(),
+                err
+            ));
+        }
+    }
+}
+
+/// Files generated from scratch (see `output` argument) do not exist before the changes are written.
+fn is_new_file(change: &FileChange) -> bool {
+    change.old.is_empty() && !change.path.exists()
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(BACKUP_SUFFIX);
+    PathBuf::from(backup)
+}
+
+fn process_file_error(path: &Path) -> SourcegenErrorKind {
+    SourcegenErrorKind::ProcessFile(path.display().to_string())
+}
@@ -1,7 +1,11 @@
-use crate::SourceGenerator;
+use crate::{GeneratorContext, SiteItem, SourceGenerator};
 use proc_macro2::{Span, TokenStream};
+use quote::ToTokens;
 use syn::spanned::Spanned;
-use syn::{Attribute, AttributeArgs, Item};
+use syn::{
+    Attribute, AttributeArgs, Block, File, ImplItem, Item, ItemFn, ItemImpl, ItemTrait, Stmt,
+    TraitItem, TraitItemMethod, Visibility,
+};
 
 pub fn item_attributes(item: &mut syn::Item) -> Option<&mut Vec<Attribute>> {
     Some(match item {
@@ -25,6 +29,28 @@ pub fn item_attributes(item: &mut syn::Item) -> Option<&mut Vec<Attribute>> {
     })
 }
 
+pub fn item_attributes_ref(item: &syn::Item) -> Option<&[Attribute]> {
+    Some(match item {
+        Item::ExternCrate(item) => &item.attrs,
+        Item::Use(item) => &item.attrs,
+        Item::Static(item) => &item.attrs,
+        Item::Const(item) => &item.attrs,
+        Item::Fn(item) => &item.attrs,
+        Item::Mod(item) => &item.attrs,
+        Item::ForeignMod(item) => &item.attrs,
+        Item::Type(item) => &item.attrs,
+        Item::Struct(item) => &item.attrs,
+        Item::Enum(item) => &item.attrs,
+        Item::Union(item) => &item.attrs,
+        Item::Trait(item) => &item.attrs,
+        Item::TraitAlias(item) => &item.attrs,
+        Item::Impl(item) => &item.attrs,
+        Item::Macro(item) => &item.attrs,
+        Item::Macro2(item) => &item.attrs,
+        _ => return None,
+    })
+}
+
 pub fn item_end_span(item: &Item) -> Span {
     match item {
         Item::ExternCrate(item) => item.semi_token.span,
@@ -63,33 +89,403 @@ pub fn item_end_span(item: &Item) -> Span {
             }
         }
         Item::Macro2(item) => item.rules.span(),
-        _ => unreachable!(),
+        // Items `syn` does not parse (`Item::Verbatim`)
+        item => item.span(),
     }
 }
 
-pub fn invoke_generator(
-    item: &Item,
-    args: AttributeArgs,
-    generator: &dyn SourceGenerator,
-) -> Result<Option<TokenStream>, anyhow::Error> {
-    match item {
-        //        ExternCrate(ItemExternCrate),
-        //        Use(ItemUse),
-        //        Static(ItemStatic),
-        //        Const(ItemConst),
-        //        Fn(ItemFn),
-        Item::Mod(item) => generator.generate_mod(args, item),
-        //        ForeignMod(ItemForeignMod),
-        //        Type(ItemType),
-        Item::Struct(item) => generator.generate_struct(args, item),
-        Item::Enum(item) => generator.generate_enum(args, item),
-        //        Union(ItemUnion),
-        Item::Trait(item) => generator.generate_trait(args, item),
-        //        Impl(ItemImpl),
-        //        Macro(ItemMacro),
-        //        Macro2(ItemMacro2),
-        //        Verbatim(ItemVerbatim),
-        // FIXME: support other?
-        _ => return Ok(None),
+/// Syntax node that can be annotated via `#[sourcegen]`: an item, or an item inside impl block or
+/// trait definition.
+pub trait Node: Sized + ToTokens {
+    /// Node this node is nested in, given to the generators as a context.
+    type Parent;
+
+    /// Code surrounding the node to make it a valid top-level item for formatting.
+    const WRAPPER: Option<&'static str>;
+
+    fn attributes(&mut self) -> Option<&mut Vec<Attribute>>;
+
+    fn attributes_ref(&self) -> Option<&[Attribute]>;
+
+    fn end_span(&self) -> Span;
+
+    fn invoke_generator(
+        &self,
+        parent: &Self::Parent,
+        context: &GeneratorContext,
+        args: AttributeArgs,
+        generator: &dyn SourceGenerator,
+    ) -> Result<Option<TokenStream>, anyhow::Error>;
+
+    /// Parse output of a generator as a list of nodes.
+    fn parse_list(tokens: TokenStream) -> syn::Result<Vec<Self>>;
+
+    /// Item of a module, if this node is one (generated code can only be redirected into a separate
+    /// file for these).
+    fn as_module_item(&self) -> Option<&Item> {
+        None
+    }
+
+    /// Annotated item given to the generators in the collect phase.
+    fn site_item<'a>(&'a self, parent: &'a Self::Parent) -> Option<SiteItem<'a>>;
+
+    /// Item of a module or of a function body, if this node is one.
+    fn as_item(&self) -> Option<&Item> {
+        None
+    }
+
+    /// Item which can have other items nested in it (modules, impl blocks and traits).
+    fn as_item_mut(&mut self) -> Option<&mut Item> {
+        None
+    }
+
+    /// Function (or method) this node is, given to the generators of the expressions in its body.
+    fn as_function(&self) -> Option<ItemFn> {
+        None
+    }
+
+    /// Function body, if this node is a function (or a method).
+    fn block(&self) -> Option<&Block> {
+        None
+    }
+
+    /// Function body which can have items nested in it.
+    fn block_mut(&mut self) -> Option<&mut Block> {
+        None
+    }
+}
+
+impl Node for Item {
+    type Parent = ();
+
+    const WRAPPER: Option<&'static str> = None;
+
+    fn attributes(&mut self) -> Option<&mut Vec<Attribute>> {
+        item_attributes(self)
+    }
+
+    fn attributes_ref(&self) -> Option<&[Attribute]> {
+        item_attributes_ref(self)
+    }
+
+    fn end_span(&self) -> Span {
+        item_end_span(self)
+    }
+
+    fn invoke_generator(
+        &self,
+        _parent: &(),
+        context: &GeneratorContext,
+        args: AttributeArgs,
+        generator: &dyn SourceGenerator,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        generator.generate_item(context, args, self)
+    }
+
+    fn site_item<'a>(&'a self, _parent: &'a ()) -> Option<SiteItem<'a>> {
+        Some(SiteItem::Item(self))
+    }
+
+    fn parse_list(tokens: TokenStream) -> syn::Result<Vec<Self>> {
+        Ok(syn::parse2::<File>(tokens)?.items)
+    }
+
+    fn as_module_item(&self) -> Option<&Item> {
+        Some(self)
+    }
+
+    fn as_item(&self) -> Option<&Item> {
+        Some(self)
+    }
+
+    fn as_item_mut(&mut self) -> Option<&mut Item> {
+        Some(self)
+    }
+
+    fn as_function(&self) -> Option<ItemFn> {
+        match self {
+            Item::Fn(item) => Some(item.clone()),
+            _ => None,
+        }
+    }
+
+    fn block(&self) -> Option<&Block> {
+        match self {
+            Item::Fn(item) => Some(&item.block),
+            _ => None,
+        }
+    }
+
+    fn block_mut(&mut self) -> Option<&mut Block> {
+        match self {
+            Item::Fn(item) => Some(&mut item.block),
+            _ => None,
+        }
+    }
+}
+
+impl Node for ImplItem {
+    type Parent = ItemImpl;
+
+    const WRAPPER: Option<&'static str> = Some("impl __Sourcegen");
+
+    fn attributes(&mut self) -> Option<&mut Vec<Attribute>> {
+        Some(match self {
+            ImplItem::Const(item) => &mut item.attrs,
+            ImplItem::Method(item) => &mut item.attrs,
+            ImplItem::Type(item) => &mut item.attrs,
+            ImplItem::Macro(item) => &mut item.attrs,
+            _ => return None,
+        })
+    }
+
+    fn attributes_ref(&self) -> Option<&[Attribute]> {
+        Some(match self {
+            ImplItem::Const(item) => &item.attrs,
+            ImplItem::Method(item) => &item.attrs,
+            ImplItem::Type(item) => &item.attrs,
+            ImplItem::Macro(item) => &item.attrs,
+            _ => return None,
+        })
+    }
+
+    fn end_span(&self) -> Span {
+        match self {
+            ImplItem::Const(item) => item.semi_token.span,
+            ImplItem::Method(item) => item.block.brace_token.span,
+            ImplItem::Type(item) => item.semi_token.span,
+            ImplItem::Macro(item) => {
+                if let Some(semi) = item.semi_token {
+                    semi.span
+                } else {
+                    item.mac.span()
+                }
+            }
+            item => item.span(),
+        }
+    }
+
+    fn invoke_generator(
+        &self,
+        parent: &ItemImpl,
+        context: &GeneratorContext,
+        args: AttributeArgs,
+        generator: &dyn SourceGenerator,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        generator.generate_impl_item(context, args, self, parent)
+    }
+
+    fn site_item<'a>(&'a self, parent: &'a ItemImpl) -> Option<SiteItem<'a>> {
+        Some(SiteItem::ImplItem(self, parent))
+    }
+
+    fn parse_list(tokens: TokenStream) -> syn::Result<Vec<Self>> {
+        let wrapper = syn::parse2::<ItemImpl>(quote::quote!(impl __Sourcegen { #tokens }))?;
+        Ok(wrapper.items)
+    }
+
+    fn as_function(&self) -> Option<ItemFn> {
+        match self {
+            ImplItem::Method(item) => Some(ItemFn {
+                attrs: item.attrs.clone(),
+                vis: item.vis.clone(),
+                sig: item.sig.clone(),
+                block: Box::new(item.block.clone()),
+            }),
+            _ => None,
+        }
+    }
+
+    fn block(&self) -> Option<&Block> {
+        match self {
+            ImplItem::Method(item) => Some(&item.block),
+            _ => None,
+        }
+    }
+
+    fn block_mut(&mut self) -> Option<&mut Block> {
+        match self {
+            ImplItem::Method(item) => Some(&mut item.block),
+            _ => None,
+        }
+    }
+}
+
+impl Node for TraitItem {
+    type Parent = ItemTrait;
+
+    const WRAPPER: Option<&'static str> = Some("trait __Sourcegen");
+
+    fn attributes(&mut self) -> Option<&mut Vec<Attribute>> {
+        Some(match self {
+            TraitItem::Const(item) => &mut item.attrs,
+            TraitItem::Method(item) => &mut item.attrs,
+            TraitItem::Type(item) => &mut item.attrs,
+            TraitItem::Macro(item) => &mut item.attrs,
+            _ => return None,
+        })
+    }
+
+    fn attributes_ref(&self) -> Option<&[Attribute]> {
+        Some(match self {
+            TraitItem::Const(item) => &item.attrs,
+            TraitItem::Method(item) => &item.attrs,
+            TraitItem::Type(item) => &item.attrs,
+            TraitItem::Macro(item) => &item.attrs,
+            _ => return None,
+        })
+    }
+
+    fn end_span(&self) -> Span {
+        match self {
+            TraitItem::Const(item) => item.semi_token.span,
+            TraitItem::Method(item) => match item.default {
+                Some(ref block) => block.brace_token.span,
+                None => item.semi_token.unwrap().span,
+            },
+            TraitItem::Type(item) => item.semi_token.span,
+            TraitItem::Macro(item) => {
+                if let Some(semi) = item.semi_token {
+                    semi.span
+                } else {
+                    item.mac.span()
+                }
+            }
+            item => item.span(),
+        }
+    }
+
+    fn invoke_generator(
+        &self,
+        parent: &ItemTrait,
+        context: &GeneratorContext,
+        args: AttributeArgs,
+        generator: &dyn SourceGenerator,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        generator.generate_trait_item(context, args, self, parent)
+    }
+
+    fn site_item<'a>(&'a self, parent: &'a ItemTrait) -> Option<SiteItem<'a>> {
+        Some(SiteItem::TraitItem(self, parent))
+    }
+
+    fn parse_list(tokens: TokenStream) -> syn::Result<Vec<Self>> {
+        let wrapper = syn::parse2::<ItemTrait>(quote::quote!(trait __Sourcegen { #tokens }))?;
+        Ok(wrapper.items)
+    }
+
+    fn as_function(&self) -> Option<ItemFn> {
+        match self {
+            TraitItem::Method(TraitItemMethod {
+                attrs,
+                sig,
+                default: Some(block),
+                ..
+            }) => Some(ItemFn {
+                attrs: attrs.clone(),
+                vis: Visibility::Inherited,
+                sig: sig.clone(),
+                block: Box::new(block.clone()),
+            }),
+            _ => None,
+        }
+    }
+
+    fn block(&self) -> Option<&Block> {
+        match self {
+            TraitItem::Method(item) => item.default.as_ref(),
+            _ => None,
+        }
+    }
+
+    fn block_mut(&mut self) -> Option<&mut Block> {
+        match self {
+            TraitItem::Method(item) => item.default.as_mut(),
+            _ => None,
+        }
+    }
+}
+
+/// Statements of function bodies: only items declared in them can be generation sites.
+impl Node for Stmt {
+    type Parent = ();
+
+    const WRAPPER: Option<&'static str> = None;
+
+    fn attributes(&mut self) -> Option<&mut Vec<Attribute>> {
+        match self {
+            Stmt::Item(item) => item_attributes(item),
+            _ => None,
+        }
+    }
+
+    fn attributes_ref(&self) -> Option<&[Attribute]> {
+        match self {
+            Stmt::Item(item) => item_attributes_ref(item),
+            _ => None,
+        }
+    }
+
+    fn end_span(&self) -> Span {
+        match self {
+            Stmt::Item(item) => item_end_span(item),
+            stmt => stmt.span(),
+        }
+    }
+
+    fn invoke_generator(
+        &self,
+        _parent: &(),
+        context: &GeneratorContext,
+        args: AttributeArgs,
+        generator: &dyn SourceGenerator,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        match self {
+            Stmt::Item(item) => generator.generate_item(context, args, item),
+            _ => Ok(None),
+        }
+    }
+
+    fn site_item<'a>(&'a self, _parent: &'a ()) -> Option<SiteItem<'a>> {
+        self.as_item().map(SiteItem::Item)
+    }
+
+    fn parse_list(tokens: TokenStream) -> syn::Result<Vec<Self>> {
+        Ok(syn::parse2::<Block>(quote::quote!({ #tokens }))?.stmts)
+    }
+
+    fn as_item(&self) -> Option<&Item> {
+        match self {
+            Stmt::Item(item) => Some(item),
+            _ => None,
+        }
+    }
+
+    fn as_item_mut(&mut self) -> Option<&mut Item> {
+        match self {
+            Stmt::Item(item) => Some(item),
+            _ => None,
+        }
+    }
+
+    fn as_function(&self) -> Option<ItemFn> {
+        match self {
+            Stmt::Item(item) => item.as_function(),
+            _ => None,
+        }
+    }
+
+    fn block(&self) -> Option<&Block> {
+        match self {
+            Stmt::Item(item) => item.block(),
+            _ => None,
+        }
+    }
+
+    fn block_mut(&mut self) -> Option<&mut Block> {
+        match self {
+            Stmt::Item(item) => item.block_mut(),
+            _ => None,
+        }
     }
 }
@@ -0,0 +1,183 @@
+//! Machine-readable report of a source generation run.
+use crate::error::{Location, SourcegenError, SourcegenErrorKind};
+use crate::vfs::FileSystem;
+use anyhow::Context;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Report of a single run, written as JSON.
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    /// Number of times the generation ran (more than one in the `fixpoint` mode).
+    iterations: usize,
+    /// Packages source code was generated for.
+    packages: Vec<String>,
+    /// Every generation site that was visited.
+    sites: Vec<SiteReport>,
+    /// Total time spent in each generator.
+    generators: BTreeMap<String, GeneratorTiming>,
+    /// Every `rustfmt` invocation.
+    rustfmt: Vec<RustfmtReport>,
+    /// All errors, including the ones not related to any particular site.
+    errors: Vec<ErrorReport>,
+    /// Warnings, like orphaned generated items.
+    warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SiteReport {
+    /// Iteration the site was visited on, starting from 1.
+    iteration: usize,
+    file: PathBuf,
+    start: Position,
+    end: Position,
+    /// Name of the generator, if the invocation was valid enough to find one.
+    generator: Option<String>,
+    /// Arguments of the `#[sourcegen]` attribute, as written in the source.
+    args: String,
+    status: SiteStatus,
+    error: Option<ErrorReport>,
+    /// Time spent in the generator, in seconds.
+    seconds: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Position {
+    line: usize,
+    column: usize,
+}
+
+/// Status of a single generation site.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SiteStatus {
+    Unchanged,
+    Updated,
+    Failed,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct GeneratorTiming {
+    calls: usize,
+    seconds: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct RustfmtReport {
+    iteration: usize,
+    file: PathBuf,
+    seconds: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ErrorReport {
+    /// Name of the `SourcegenErrorKind` variant, if error is one of ours.
+    kind: Option<&'static str>,
+    message: String,
+}
+
+impl ErrorReport {
+    fn new(err: &SourcegenError) -> Self {
+        ErrorReport {
+            kind: err
+                .downcast_ref::<SourcegenErrorKind>()
+                .map(SourcegenErrorKind::name),
+            message: format!("{:#}", err),
+        }
+    }
+}
+
+/// Index of the site in the report.
+#[derive(Debug, Clone, Copy)]
+pub struct SiteId(usize);
+
+impl Report {
+    /// Start the next iteration of the generation. Sites and `rustfmt` calls are recorded with
+    /// the iteration number, so sites visited on every iteration can be told apart.
+    pub fn iteration_started(&mut self) {
+        self.iterations += 1;
+    }
+
+    pub fn package_started(&mut self, name: &str) {
+        if !self.packages.iter().any(|p| p == name) {
+            self.packages.push(name.to_owned());
+        }
+    }
+
+    /// Add a new site; its status is `unchanged` until it is updated.
+    pub fn site_started(
+        &mut self,
+        location: &Location,
+        generator: Option<&str>,
+        args: String,
+    ) -> SiteId {
+        self.sites.push(SiteReport {
+            iteration: self.iterations,
+            file: location.path().to_owned(),
+            start: Position {
+                line: location.start().line,
+                column: location.start().column,
+            },
+            end: Position {
+                line: location.end().line,
+                column: location.end().column,
+            },
+            generator: generator.map(str::to_owned),
+            args,
+            status: SiteStatus::Unchanged,
+            error: None,
+            seconds: 0.0,
+        });
+        SiteId(self.sites.len() - 1)
+    }
+
+    pub fn site_generated(&mut self, site: SiteId, duration: Duration) {
+        let site = &mut self.sites[site.0];
+        site.seconds = duration.as_secs_f64();
+        if let Some(ref generator) = site.generator {
+            let timing = self.generators.entry(generator.clone()).or_default();
+            timing.calls += 1;
+            timing.seconds += site.seconds;
+        }
+    }
+
+    pub fn site_updated(&mut self, site: SiteId) {
+        self.sites[site.0].status = SiteStatus::Updated;
+    }
+
+    pub fn site_failed(&mut self, site: SiteId, err: &SourcegenError) {
+        let site = &mut self.sites[site.0];
+        site.status = SiteStatus::Failed;
+        site.error = Some(ErrorReport::new(err));
+    }
+
+    pub fn rustfmt_finished(&mut self, file: &Path, duration: Duration) {
+        self.rustfmt.push(RustfmtReport {
+            iteration: self.iterations,
+            file: file.to_owned(),
+            seconds: duration.as_secs_f64(),
+        });
+    }
+
+    pub fn error(&mut self, err: &SourcegenError) {
+        self.errors.push(ErrorReport::new(err));
+    }
+
+    pub fn warning(&mut self, message: &str) {
+        // Same warnings are found again on every iteration
+        if !self.warnings.iter().any(|w| w == message) {
+            self.warnings.push(message.to_owned());
+        }
+    }
+
+    /// Write report as JSON to the given file.
+    pub fn write(&self, fs: &dyn FileSystem, path: &Path) -> Result<(), SourcegenError> {
+        let json = serde_json::to_string_pretty(self)
+            .with_context(|| SourcegenErrorKind::WriteReport(path.display().to_string()))?;
+        fs.write(path, &json)
+            .with_context(|| SourcegenErrorKind::WriteReport(path.display().to_string()))?;
+        Ok(())
+    }
+}
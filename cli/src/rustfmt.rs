@@ -11,7 +11,8 @@ pub struct Formatter {
 
 impl Formatter {
     pub fn new(root: &Path) -> Result<Self, SourcegenError> {
-        let basedir = dunce::canonicalize(root).context(SourcegenErrorKind::WhichRustFmtFailed)?;
+        let basedir =
+            dunce::canonicalize(non_empty(root)).context(SourcegenErrorKind::WhichRustFmtFailed)?;
         let output = Command::new("rustup")
             .current_dir(basedir)
             .arg("which")
@@ -35,7 +36,7 @@ impl Formatter {
         basefile: &Path,
         content: impl std::fmt::Display,
     ) -> Result<String, SourcegenError> {
-        let basedir = dunce::canonicalize(basefile.parent().unwrap())
+        let basedir = dunce::canonicalize(non_empty(basefile.parent().unwrap()))
             .context(SourcegenErrorKind::RustFmtFailed)?;
         let mut rustfmt = Command::new(&self.rustfmt)
             .current_dir(basedir)
@@ -54,6 +55,15 @@ impl Formatter {
     }
 }
 
+/// Directories that do not exist (files only kept in memory, see `vfs`) are replaced by their
+/// nearest existing ancestor, so `rustfmt.toml` of the crate is still picked up. Relative paths
+/// like `lib.rs` have an empty parent, which we treat as the current directory.
+fn non_empty(dir: &Path) -> &Path {
+    dir.ancestors()
+        .find(|dir| !dir.as_os_str().is_empty() && dir.is_dir())
+        .unwrap_or_else(|| Path::new("."))
+}
+
 fn rustfmt_output(output: Output) -> Result<String, SourcegenError> {
     if output.status.success() {
         let result = String::from_utf8(output.stdout).context(SourcegenErrorKind::RustFmtFailed)?;
@@ -0,0 +1,133 @@
+use crate::error::{SourcegenError, SourcegenErrors};
+use crate::header::{FILE_HEADER, ITEM_HEADER};
+use crate::observer::{Observer, DEFAULT_OBSERVER};
+use crate::report::Report;
+use crate::vfs::{FileSystem, REAL_FILE_SYSTEM};
+use crate::{GeneratorsMap, SourcegenParameters};
+use std::path::Path;
+
+/// State shared by all the files processed during a single run.
+pub struct Session<'a> {
+    pub generators: GeneratorsMap<'a>,
+    pub report: Report,
+    pub observer: &'a dyn Observer,
+    /// Header templates given in the parameters.
+    pub item_header: Option<&'a str>,
+    pub file_header: Option<&'a str>,
+    /// Remove generated items and headers not belonging to any invocation.
+    pub remove_orphans: bool,
+    /// Run the expansion of every file twice and fail if the second pass changes anything.
+    pub verify: bool,
+    /// File system the files are read from and written to.
+    pub fs: &'a dyn FileSystem,
+    /// Collect errors and keep going instead of stopping at the first one.
+    keep_going: bool,
+    /// Where to write the report at the end of the run.
+    report_path: Option<&'a Path>,
+    errors: Vec<SourcegenError>,
+}
+
+impl<'a> Session<'a> {
+    pub fn new(parameters: &SourcegenParameters<'a>) -> Self {
+        let mut report = Report::default();
+        report.iteration_started();
+        Session {
+            generators: parameters.generators.iter().cloned().collect(),
+            report,
+            observer: parameters.observer.unwrap_or(&DEFAULT_OBSERVER),
+            item_header: parameters.item_header,
+            file_header: parameters.file_header,
+            remove_orphans: parameters.remove_orphans,
+            verify: parameters.verify,
+            fs: parameters.fs.unwrap_or(&REAL_FILE_SYSTEM),
+            keep_going: parameters.keep_going,
+            report_path: parameters.report,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Let generators know the collect phase starts.
+    pub fn collect_started(&self) {
+        for generator in self.generators.values() {
+            generator.collect_started();
+        }
+    }
+
+    /// Report a warning to the observer and in the report.
+    pub fn warning(&mut self, message: &str) {
+        self.observer.warning(message);
+        self.report.warning(message);
+    }
+
+    /// All the header templates that could be used in this run: defaults, the ones given in the
+    /// parameters and the ones provided by generators.
+    pub fn header_templates(&self) -> Vec<&'a str> {
+        let mut templates = vec![ITEM_HEADER, FILE_HEADER];
+        templates.extend(self.item_header);
+        templates.extend(self.file_header);
+        for generator in self.generators.values() {
+            templates.extend(generator.item_header());
+            templates.extend(generator.file_header());
+        }
+        templates
+    }
+
+    /// Handle the result of processing a single unit of work (package, file or a generation site).
+    /// In the keep-going mode, errors are recorded and `Ok(None)` is returned so the caller can
+    /// skip that unit and continue; otherwise, errors are returned as-is.
+    pub fn recover<T>(
+        &mut self,
+        result: Result<T, SourcegenError>,
+    ) -> Result<Option<T>, SourcegenError> {
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(err) if self.keep_going => {
+                self.observer.error(&err);
+                self.report.error(&err);
+                self.errors.push(err);
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Run the closure without reporting anything (used for the verification pass): events are
+    /// dropped and errors are returned instead of being recorded in the keep-going mode.
+    pub fn silently<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let observer = std::mem::replace(&mut self.observer, &SilentObserver);
+        let report = std::mem::take(&mut self.report);
+        let keep_going = std::mem::replace(&mut self.keep_going, false);
+        let result = f(self);
+        self.observer = observer;
+        self.report = report;
+        self.keep_going = keep_going;
+        result
+    }
+
+    /// Check if any errors were recorded in the keep-going mode.
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    /// Finish the session with the result of the run. Writes the report, if requested, and
+    /// returns all the errors recorded in the keep-going mode.
+    pub fn finish<T>(mut self, result: Result<T, SourcegenError>) -> Result<T, SourcegenError> {
+        if let Err(ref err) = result {
+            self.report.error(err);
+        }
+        if let Some(path) = self.report_path {
+            self.report.write(self.fs, path)?;
+        }
+        let value = result?;
+        if self.errors.is_empty() {
+            Ok(value)
+        } else {
+            Err(SourcegenErrors::new(self.errors).into())
+        }
+    }
+}
+
+/// Observer dropping all the events.
+struct SilentObserver;
+
+impl Observer for SilentObserver {}
@@ -0,0 +1,244 @@
+//! Helpers for testing source generators.
+//!
+//! Golden tests are directories with `input` crate (with `Cargo.toml`) and either `expected`
+//! directory with the expected content of the crate after the generation, or `expected-error.txt`
+//! file with the expected error message. Paths in the error message are relative to the test
+//! directory (for example, `input/src/lib.rs`).
+//!
+//! Set `SOURCEGEN_BLESS` environment variable to update expected outputs instead of comparing them.
+use crate::error::SourcegenError;
+use crate::{SourceGenerator, SourcegenParameters};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Environment variable that makes golden tests to update expected outputs instead of comparing
+/// them.
+pub const BLESS_ENV: &str = "SOURCEGEN_BLESS";
+
+/// Name of the file with the expected error message in the golden test directory.
+pub const EXPECTED_ERROR: &str = "expected-error.txt";
+
+/// If golden tests should update expected outputs (`SOURCEGEN_BLESS` is set to anything but `0`).
+pub fn is_blessing() -> bool {
+    std::env::var_os(BLESS_ENV).is_some_and(|value| value != "0")
+}
+
+/// Expand source code of a single file in memory with given generators. Out-of-line modules are
+/// not followed.
+pub fn expand(
+    source: &str,
+    generators: &[(&str, &dyn SourceGenerator)],
+) -> Result<String, SourcegenError> {
+    let parameters = SourcegenParameters {
+        generators,
+        ..Default::default()
+    };
+    crate::generate_source(Path::new("src/lib.rs"), source, &parameters)
+}
+
+/// Expand source code via [`expand`] and assert that the result is the same as `expected`.
+///
+/// [`expand`]: fn.expand.html
+pub fn assert_expands_to(
+    source: &str,
+    generators: &[(&str, &dyn SourceGenerator)],
+    expected: &str,
+) {
+    let output = match expand(source, generators) {
+        Ok(output) => output,
+        Err(err) => panic!("failed to expand source: {:#}", err),
+    };
+    pretty_assertions::assert_eq!(PrettyString(&output), PrettyString(expected));
+}
+
+/// Expand source code via [`expand`] and return the error. Panics if the expansion succeeds.
+/// Use `downcast_ref::<SourcegenErrorKind>()` to check the kind of the error.
+///
+/// [`expand`]: fn.expand.html
+pub fn expand_error(source: &str, generators: &[(&str, &dyn SourceGenerator)]) -> SourcegenError {
+    match expand(source, generators) {
+        Ok(output) => panic!("expected expansion to fail, but it produced:\n{}", output),
+        Err(err) => err,
+    }
+}
+
+/// Run every golden test directory under `root` (directories that have `input` subdirectory).
+pub fn run_golden_dirs(root: &Path, parameters: &SourcegenParameters) -> Result<(), anyhow::Error> {
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(root)? {
+        let path = entry?.path();
+        if path.join("input").is_dir() {
+            dirs.push(path);
+        }
+    }
+    dirs.sort();
+    for dir in dirs {
+        run_golden_dir(&dir, parameters)?;
+    }
+    Ok(())
+}
+
+/// Run golden test in the given directory: generate code for the `input` crate (given
+/// `parameters`, with the manifest replaced by the one of the `input` crate) and compare the
+/// result with `expected` directory or `expected-error.txt` file. Nothing is written into `input`
+/// directory. Generation runs once (`fixpoint` parameter is not applied).
+pub fn run_golden_dir(dir: &Path, parameters: &SourcegenParameters) -> Result<(), anyhow::Error> {
+    let input = dunce::canonicalize(dir.join("input"))?;
+    let manifest = input.join("Cargo.toml");
+    let mut parameters = parameters.clone();
+    parameters.manifest = Some(&manifest);
+
+    let error_path = dir.join(EXPECTED_ERROR);
+    match crate::plan_sourcegen(&parameters) {
+        Ok(changes) => {
+            assert!(
+                is_blessing() || !error_path.exists(),
+                "expected generation in '{}' to fail, but it succeeded",
+                dir.display()
+            );
+            let mut actual = read_tree(&input)?;
+            for change in changes {
+                let relative = change.path.strip_prefix(&input)?.to_owned();
+                actual.insert(relative, change.new);
+            }
+            let expected = dir.join("expected");
+            if is_blessing() {
+                write_tree(&expected, &actual)?;
+                remove_file_if_exists(&error_path)?;
+            } else {
+                assert_tree_matches(&actual, &expected)?;
+            }
+        }
+        Err(err) => {
+            let message = format!("{:#}", err)
+                .replace(&format!("{}", input.display()), "input")
+                .replace('\\', "/");
+            if is_blessing() {
+                std::fs::write(&error_path, format!("{}\n", message))?;
+                if dir.join("expected").exists() {
+                    std::fs::remove_dir_all(dir.join("expected"))?;
+                }
+            } else if error_path.exists() {
+                let expected = std::fs::read_to_string(&error_path)?;
+                pretty_assertions::assert_eq!(
+                    PrettyString(&message),
+                    PrettyString(expected.trim_end()),
+                    "error of generation in '{}' differs from '{}'",
+                    dir.display(),
+                    EXPECTED_ERROR
+                );
+            } else {
+                return Err(err);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Assert that all files at `expected` path exist at `actual` path and the contents of the files
+/// is the same (`Cargo.lock` files in `actual` are ignored). With `SOURCEGEN_BLESS` set, copy the
+/// `actual` files into `expected` instead.
+pub fn assert_matches_expected(actual: &Path, expected: &Path) -> Result<(), anyhow::Error> {
+    let actual = read_tree(actual)?;
+    if is_blessing() {
+        write_tree(expected, &actual)
+    } else {
+        assert_tree_matches(&actual, expected)
+    }
+}
+
+fn assert_tree_matches(
+    actual: &BTreeMap<PathBuf, String>,
+    expected: &Path,
+) -> Result<(), anyhow::Error> {
+    let expected_files = read_tree(expected)?;
+    for path in expected_files.keys() {
+        assert!(
+            actual.contains_key(path),
+            "expected file '{}' does not exist in actual output",
+            path.display()
+        );
+    }
+    for (path, actual_content) in actual {
+        let expected_content = match expected_files.get(path) {
+            Some(content) => content,
+            None => panic!(
+                "actual file '{}' does not exist in expected output '{}'",
+                path.display(),
+                expected.display()
+            ),
+        };
+        pretty_assertions::assert_eq!(
+            PrettyString(actual_content),
+            PrettyString(expected_content),
+            "contents of actual file '{}' differs from the contents of expected file",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Read all files of the directory by their relative path (skipping `Cargo.lock` and `target`).
+fn read_tree(root: &Path) -> Result<BTreeMap<PathBuf, String>, anyhow::Error> {
+    fn visit(
+        root: &Path,
+        dir: &Path,
+        files: &mut BTreeMap<PathBuf, String>,
+    ) -> Result<(), anyhow::Error> {
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default();
+            if name == "Cargo.lock" || name == "target" {
+                continue;
+            }
+            if path.is_dir() {
+                visit(root, &path, files)?;
+            } else {
+                let content = std::fs::read_to_string(&path)?;
+                files.insert(path.strip_prefix(root)?.to_owned(), content);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = BTreeMap::new();
+    if root.is_dir() {
+        visit(root, root, &mut files)?;
+    }
+    Ok(files)
+}
+
+/// Replace the content of the directory with given files.
+fn write_tree(root: &Path, files: &BTreeMap<PathBuf, String>) -> Result<(), anyhow::Error> {
+    if root.exists() {
+        std::fs::remove_dir_all(root)?;
+    }
+    for (path, content) in files {
+        let path = root.join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, content)?;
+    }
+    Ok(())
+}
+
+fn remove_file_if_exists(path: &Path) -> Result<(), anyhow::Error> {
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Wrapper around string slice that makes debug output `{:?}` to print string same way as `{}`.
+/// Used in different `assert*!` macros in combination with `pretty_assertions` crate to make
+/// test failures to show nice diffs.
+#[derive(PartialEq, Eq)]
+pub struct PrettyString<'a>(pub &'a str);
+
+/// Make diff to display string as multi-line string
+impl<'a> std::fmt::Debug for PrettyString<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(self.0)
+    }
+}
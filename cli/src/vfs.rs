@@ -0,0 +1,175 @@
+//! File system abstraction used for all reads and writes of the source files.
+//!
+//! [`RealFileSystem`] is the default one. [`MemoryFileSystem`] keeps files in memory, optionally on
+//! top of another file system (for example, to generate code against unsaved editor buffers
+//! without touching the files on disk).
+//!
+//! [`RealFileSystem`]: struct.RealFileSystem.html
+//! [`MemoryFileSystem`]: struct.MemoryFileSystem.html
+use crate::error::{SourcegenError, SourcegenErrorKind};
+use crate::observer::Observer;
+use crate::plan::FileChange;
+use anyhow::Context;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File system the source files are read from and written to.
+pub trait FileSystem {
+    /// Read the whole file as a string.
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Read the whole file as bytes.
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.read_to_string(path).map(String::into_bytes)
+    }
+
+    /// Write the whole file, creating it (and its parent directories) if it does not exist.
+    fn write(&self, path: &Path, content: &str) -> io::Result<()>;
+
+    /// If the file exists.
+    fn is_file(&self, path: &Path) -> bool;
+
+    /// Write all changes, failing with `FileModified` error if any of the files changed since the
+    /// changes were planned. By default, files are written one by one.
+    fn write_changes(
+        &self,
+        changes: &[FileChange],
+        observer: &dyn Observer,
+    ) -> Result<(), SourcegenError> {
+        for change in changes {
+            let current = if change.old.is_empty() && !self.is_file(&change.path) {
+                String::new()
+            } else {
+                self.read_to_string(&change.path)
+                    .with_context(|| process_file_error(&change.path))?
+            };
+            if current != change.old {
+                return Err(
+                    SourcegenErrorKind::FileModified(change.path.display().to_string()).into(),
+                );
+            }
+        }
+        for change in changes {
+            self.write(&change.path, &change.new)
+                .with_context(|| process_file_error(&change.path))?;
+            observer.file_written(&change.path);
+        }
+        Ok(())
+    }
+}
+
+/// Files on the disk. Changes are written atomically, see [`apply_changes`].
+///
+/// [`apply_changes`]: ../fn.apply_changes.html
+pub struct RealFileSystem;
+
+pub(crate) static REAL_FILE_SYSTEM: RealFileSystem = RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn write(&self, path: &Path, content: &str) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            if !dir.as_os_str().is_empty() {
+                std::fs::create_dir_all(dir)?;
+            }
+        }
+        std::fs::write(path, content)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn write_changes(
+        &self,
+        changes: &[FileChange],
+        observer: &dyn Observer,
+    ) -> Result<(), SourcegenError> {
+        crate::plan::write_changes(changes, observer)
+    }
+}
+
+/// Files kept in memory. Files not found in memory are read from the `base` file system, if any;
+/// writes always go to memory.
+#[derive(Default)]
+pub struct MemoryFileSystem<'a> {
+    files: RefCell<BTreeMap<PathBuf, String>>,
+    base: Option<&'a dyn FileSystem>,
+}
+
+impl<'a> MemoryFileSystem<'a> {
+    /// Empty file system.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overlay on top of the `base` file system (for example, [`RealFileSystem`]).
+    ///
+    /// [`RealFileSystem`]: struct.RealFileSystem.html
+    pub fn overlay(base: &'a dyn FileSystem) -> Self {
+        MemoryFileSystem {
+            files: RefCell::default(),
+            base: Some(base),
+        }
+    }
+
+    /// Put the file into memory (for example, an unsaved editor buffer).
+    pub fn insert(&self, path: impl Into<PathBuf>, content: impl Into<String>) {
+        self.files.borrow_mut().insert(path.into(), content.into());
+    }
+
+    /// Content of the file, if it is in memory (written or inserted).
+    pub fn get(&self, path: &Path) -> Option<String> {
+        self.files.borrow().get(path).cloned()
+    }
+
+    /// All files in memory along with their content.
+    pub fn files(&self) -> BTreeMap<PathBuf, String> {
+        self.files.borrow().clone()
+    }
+}
+
+impl FileSystem for MemoryFileSystem<'_> {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        if let Some(content) = self.get(path) {
+            return Ok(content);
+        }
+        match self.base {
+            Some(base) => base.read_to_string(path),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("file '{}' not found", path.display()),
+            )),
+        }
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match (self.get(path), self.base) {
+            (Some(content), _) => Ok(content.into_bytes()),
+            (None, Some(base)) => base.read(path),
+            (None, None) => self.read_to_string(path).map(String::into_bytes),
+        }
+    }
+
+    fn write(&self, path: &Path, content: &str) -> io::Result<()> {
+        self.insert(path, content);
+        Ok(())
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.files.borrow().contains_key(path) || self.base.is_some_and(|base| base.is_file(path))
+    }
+}
+
+fn process_file_error(path: &Path) -> SourcegenErrorKind {
+    SourcegenErrorKind::ProcessFile(path.display().to_string())
+}
@@ -1,7 +1,11 @@
 use proc_macro2::TokenStream;
 use quote::quote;
 use sourcegen_cli::tokens::{NewLine, PlainComment};
-use sourcegen_cli::SourceGenerator;
+use sourcegen_cli::{CollectedSite, GeneratorContext, SiteItem, SourceGenerator};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 /// Writes back the input without any changes
 pub struct WriteBack;
@@ -77,6 +81,38 @@ impl SourceGenerator for GenerateSimple {
     }
 }
 
+/// Add `#[derive(Debug)]` to the struct, keeping everything else as-is
+pub struct GenerateDerive;
+
+impl SourceGenerator for GenerateDerive {
+    fn generate_struct(
+        &self,
+        _args: syn::AttributeArgs,
+        item: &syn::ItemStruct,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        let mut item = item.clone();
+        item.attrs.push(syn::parse_quote!(#[derive(Debug)]));
+        Ok(Some(quote!(#item)))
+    }
+}
+
+/// Same as `GenerateSimple`, but with a custom header comment
+pub struct GenerateWithHeader;
+
+impl SourceGenerator for GenerateWithHeader {
+    fn generate_struct(
+        &self,
+        args: syn::AttributeArgs,
+        item: &syn::ItemStruct,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        GenerateSimple.generate_struct(args, item)
+    }
+
+    fn item_header(&self) -> Option<&str> {
+        Some("Generated by {generator}, do not edit.")
+    }
+}
+
 /// Generates a struct with a doc comment
 pub struct GenerateDocComments;
 
@@ -103,6 +139,7 @@ pub struct GenerateFile;
 impl SourceGenerator for GenerateFile {
     fn generate_file(
         &self,
+        _context: &GeneratorContext,
         _args: syn::AttributeArgs,
         _file: &syn::File,
     ) -> Result<Option<TokenStream>, anyhow::Error> {
@@ -115,6 +152,290 @@ impl SourceGenerator for GenerateFile {
     }
 }
 
+/// Generate a tree of modules, one struct per module (with `duplicate = true`, the same module is
+/// generated twice)
+pub struct GenerateModFiles;
+
+impl SourceGenerator for GenerateModFiles {
+    fn generate_mod_files(
+        &self,
+        _context: &GeneratorContext,
+        args: syn::AttributeArgs,
+        _file: &syn::File,
+    ) -> Result<Option<Vec<(PathBuf, TokenStream)>>, anyhow::Error> {
+        let duplicate = args.iter().any(|arg| match arg {
+            syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) if nv.path.is_ident("duplicate") => {
+                matches!(nv.lit, syn::Lit::Bool(ref value) if value.value)
+            }
+            _ => false,
+        });
+        if duplicate {
+            return Ok(Some(vec![
+                (
+                    "widget.rs".into(),
+                    quote!(
+                        pub struct Widget;
+                    ),
+                ),
+                (
+                    "widget/mod.rs".into(),
+                    quote!(
+                        pub struct Widget;
+                    ),
+                ),
+            ]));
+        }
+        Ok(Some(vec![
+            (
+                "widget.rs".into(),
+                quote!(
+                    pub struct Widget;
+                ),
+            ),
+            (
+                "gadget/mod.rs".into(),
+                quote!(
+                    pub struct Gadget;
+                ),
+            ),
+            (
+                "gadget/part.rs".into(),
+                quote!(
+                    pub struct Part;
+                ),
+            ),
+            (
+                "extra/deep.rs".into(),
+                quote!(
+                    pub struct Deep;
+                ),
+            ),
+        ]))
+    }
+}
+
+/// Generate getters inside impl block and a default method inside a trait (returning the path of the trait)
+pub struct GenerateMembers;
+
+impl SourceGenerator for GenerateMembers {
+    fn generate_impl_item(
+        &self,
+        _context: &GeneratorContext,
+        _args: syn::AttributeArgs,
+        item: &syn::ImplItem,
+        parent: &syn::ItemImpl,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        let self_ty = &parent.self_ty;
+        let doc = format!(" Field of `{}`", quote!(#self_ty));
+        Ok(Some(quote! {
+            #item
+
+            #[sourcegen::generated]
+            #[doc = #doc]
+            pub fn x(&self) -> u32 { self.x }
+
+            #[sourcegen::generated]
+            pub fn y(&self) -> u32 { self.y }
+        }))
+    }
+
+    fn generate_trait_item(
+        &self,
+        context: &GeneratorContext,
+        _args: syn::AttributeArgs,
+        item: &syn::TraitItem,
+        parent: &syn::ItemTrait,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        let name = format!("{}::{}", context.module_path(), parent.ident);
+        Ok(Some(quote! {
+            #item
+
+            #[sourcegen::generated]
+            fn trait_name(&self) -> &'static str { #name }
+        }))
+    }
+}
+
+/// Generate a `match` over the first argument of the function (other than `self`), mapping keys given via `keys`
+/// argument to their indices
+pub struct GenerateMatch;
+
+impl SourceGenerator for GenerateMatch {
+    fn generate_expr(
+        &self,
+        _context: &GeneratorContext,
+        args: syn::AttributeArgs,
+        function: &syn::ItemFn,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        let keys = args
+            .iter()
+            .find_map(|arg| match arg {
+                syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) if nv.path.is_ident("keys") => {
+                    match nv.lit {
+                        syn::Lit::Str(ref value) => Some(value.value()),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .ok_or_else(|| anyhow::anyhow!("missing `keys` argument"))?;
+        let arg = function
+            .sig
+            .inputs
+            .iter()
+            .find_map(|arg| match arg {
+                syn::FnArg::Typed(arg) => Some(&arg.pat),
+                syn::FnArg::Receiver(_) => None,
+            })
+            .ok_or_else(|| anyhow::anyhow!("function must have an argument"))?;
+        let keys = keys.split(',').map(str::trim);
+        let indices = 0..keys.clone().count() as u32;
+        Ok(Some(quote! {
+            match #arg {
+                #(#keys => Some(#indices),)*
+                _ => None,
+            }
+        }))
+    }
+}
+
+/// Generate an enum with a variant for every struct of the crate with the name ending in `Message`
+pub struct GenerateRegistry;
+
+impl SourceGenerator for GenerateRegistry {
+    fn generate_item(
+        &self,
+        context: &GeneratorContext,
+        _args: syn::AttributeArgs,
+        item: &syn::Item,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        let item = match item {
+            syn::Item::Enum(item) => item,
+            _ => return Ok(None),
+        };
+        let variants = context
+            .index()
+            .items()
+            .filter_map(|(module_path, item)| match item {
+                syn::Item::Struct(item) if item.ident.to_string().ends_with("Message") => {
+                    let path: syn::Path =
+                        syn::parse_str(&format!("{}::{}", module_path, item.ident))
+                            .expect("valid path");
+                    let ident = &item.ident;
+                    Some(quote!(#ident(#path)))
+                }
+                _ => None,
+            });
+        let vis = &item.vis;
+        let ident = &item.ident;
+        let doc = format!(" Messages, registered from `{}`", context.module_path());
+        Ok(Some(quote! {
+            #[doc = #doc]
+            #vis enum #ident {
+                #(#variants,)*
+            }
+        }))
+    }
+}
+
+/// Handlers collected by `GenerateDispatcher` (generators are registered as constants, so it cannot
+/// keep them itself), cleared when the collect phase starts
+static HANDLERS: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Collect structs annotated with `role = "handler"` and generate an enum over all of them for the
+/// enum annotated with `role = "dispatcher"`
+pub struct GenerateDispatcher;
+
+fn role(args: &[syn::NestedMeta]) -> Option<String> {
+    args.iter().find_map(|arg| match arg {
+        syn::NestedMeta::Meta(syn::Meta::NameValue(nv)) if nv.path.is_ident("role") => match nv.lit
+        {
+            syn::Lit::Str(ref value) => Some(value.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+impl SourceGenerator for GenerateDispatcher {
+    fn collect_started(&self) {
+        HANDLERS.lock().unwrap().clear();
+    }
+
+    fn collect(&self, site: &CollectedSite) -> Result<(), anyhow::Error> {
+        if let (Some("handler"), SiteItem::Item(syn::Item::Struct(item))) =
+            (role(&site.args).as_deref(), site.item)
+        {
+            let path = format!("{}::{}", site.module_path, item.ident);
+            HANDLERS.lock().unwrap().insert(path);
+        }
+        Ok(())
+    }
+
+    fn generate_enum(
+        &self,
+        args: syn::AttributeArgs,
+        item: &syn::ItemEnum,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        if role(&args).as_deref() != Some("dispatcher") {
+            return Ok(None);
+        }
+        let handlers = HANDLERS.lock().unwrap();
+        let variants = handlers.iter().map(|path| {
+            let path: syn::Path = syn::parse_str(path).expect("valid path");
+            let ident = &path.segments.last().unwrap().ident;
+            quote!(#ident(#path))
+        });
+        let vis = &item.vis;
+        let ident = &item.ident;
+        Ok(Some(quote! {
+            #vis enum #ident {
+                #(#variants,)*
+            }
+        }))
+    }
+}
+
+/// Generate a `<Name>Message` struct after the annotated struct
+pub struct GenerateMessage;
+
+impl SourceGenerator for GenerateMessage {
+    fn generate_struct(
+        &self,
+        _args: syn::AttributeArgs,
+        item: &syn::ItemStruct,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        let message = quote::format_ident!("{}Message", item.ident);
+        Ok(Some(quote! {
+            #item
+
+            #[sourcegen::generated]
+            pub struct #message;
+        }))
+    }
+}
+
+/// Generates different output on every run
+pub struct GenerateCounter;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+impl SourceGenerator for GenerateCounter {
+    fn generate_struct(
+        &self,
+        _args: syn::AttributeArgs,
+        item: &syn::ItemStruct,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        let counter = COUNTER.fetch_add(1, Ordering::SeqCst);
+        Ok(Some(quote! {
+            #item
+
+            #[sourcegen::generated]
+            pub const COUNTER: usize = #counter;
+        }))
+    }
+}
+
 /// Generates a struct with regular comments
 pub struct GeneratePlainComments;
 
@@ -174,3 +495,47 @@ impl SourceGenerator for GenerateTrait {
         }))
     }
 }
+
+/// Always fails
+pub struct GenerateError;
+
+impl SourceGenerator for GenerateError {
+    fn generate_struct(
+        &self,
+        _args: syn::AttributeArgs,
+        _item: &syn::ItemStruct,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        Err(anyhow::anyhow!("generator failed"))
+    }
+}
+
+/// Set the value of the constant or static item to its name
+pub struct GenerateName;
+
+impl SourceGenerator for GenerateName {
+    fn generate_const(
+        &self,
+        _args: syn::AttributeArgs,
+        item: &syn::ItemConst,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        let vis = &item.vis;
+        let ident = &item.ident;
+        let name = ident.to_string();
+        Ok(Some(quote! {
+            #vis const #ident: &str = #name;
+        }))
+    }
+
+    fn generate_static(
+        &self,
+        _args: syn::AttributeArgs,
+        item: &syn::ItemStatic,
+    ) -> Result<Option<TokenStream>, anyhow::Error> {
+        let vis = &item.vis;
+        let ident = &item.ident;
+        let name = ident.to_string();
+        Ok(Some(quote! {
+            #vis static #ident: &str = #name;
+        }))
+    }
+}
@@ -1,88 +1,9 @@
-use pretty_assertions::assert_eq as pretty_assert_eq;
+use sourcegen_cli::observer::Observer;
+use sourcegen_cli::Location;
+use std::cell::RefCell;
 use std::path::Path;
 use std::process::Command;
 
-/// Assert that all files at `expected` path exist at `actual` path and the contents of the files
-/// is the same.
-pub fn assert_matches_expected(
-    base: &Path,
-    actual: &Path,
-    expected: &Path,
-) -> Result<(), anyhow::Error> {
-    assert_eq!(
-        actual.is_file(),
-        expected.is_file(),
-        "actual '{}' is_file({}) != expected '{}' is_file({})",
-        actual.display(),
-        actual.is_file(),
-        expected.display(),
-        expected.is_file()
-    );
-
-    if actual.is_file() {
-        let actual_content = std::fs::read_to_string(actual)?;
-        let expected_content = std::fs::read_to_string(expected)?;
-        pretty_assert_eq!(
-            PrettyString(&actual_content),
-            PrettyString(&expected_content),
-            "contents of actual file '{}' differs from the contents of expected file '{}'",
-            actual.strip_prefix(base)?.display(),
-            expected.strip_prefix(base)?.display()
-        );
-    } else {
-        let mut expected_it = std::fs::read_dir(expected)?;
-        while let Some(expected_child) = expected_it.next() {
-            let expected_child = expected_child?;
-            let name = expected_child.file_name();
-
-            let actual_child = actual.join(&name);
-
-            assert!(
-                actual_child.exists(),
-                "expected file '{}' does not exists in actual output '{}'",
-                Path::new(&name).display(),
-                actual.strip_prefix(base)?.display()
-            );
-
-            assert_matches_expected(base, &actual_child, &expected_child.path())?;
-        }
-
-        // Make sure everything in `actual` also exists in `expected`
-        let mut actual_it = std::fs::read_dir(actual)?;
-        while let Some(actual_child) = actual_it.next() {
-            let actual_child = actual_child?;
-            let name = actual_child.file_name();
-            let expected_child = expected.join(&name);
-
-            // We ignore Cargo.lock
-            if name != "Cargo.lock" {
-                assert!(
-                    expected_child.exists(),
-                    "actual file '{}' does not exists in expected output '{}'",
-                    Path::new(&name).display(),
-                    actual.strip_prefix(base)?.display()
-                );
-            }
-        }
-    }
-
-    Ok(())
-}
-
-/// Wrapper around string slice that makes debug output `{:?}` to print string same way as `{}`.
-/// Used in different `assert*!` macros in combination with `pretty_assertions` crate to make
-/// test failures to show nice diffs.
-#[derive(PartialEq, Eq)]
-#[doc(hidden)]
-pub struct PrettyString<'a>(pub &'a str);
-
-/// Make diff to display string as multi-line string
-impl<'a> std::fmt::Debug for PrettyString<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        f.write_str(self.0)
-    }
-}
-
 /// Install `rustfmt` component so our generator can use it.
 pub fn install_rustfmt(path: &Path) -> Result<(), anyhow::Error> {
     let output = Command::new("rustup")
@@ -103,3 +24,45 @@ pub fn install_rustfmt(path: &Path) -> Result<(), anyhow::Error> {
     }
     Ok(())
 }
+
+/// Observer that records all events as strings (with file names only, to keep them short).
+#[derive(Default)]
+pub struct RecordingObserver {
+    pub events: RefCell<Vec<String>>,
+}
+
+impl RecordingObserver {
+    fn record(&self, event: &str, path: Option<&Path>) {
+        let mut event = event.to_owned();
+        if let Some(name) = path.and_then(Path::file_name) {
+            event = format!("{} {}", event, name.to_string_lossy());
+        }
+        self.events.borrow_mut().push(event);
+    }
+}
+
+impl Observer for RecordingObserver {
+    fn package_started(&self, name: &str) {
+        self.record(&format!("package {}", name), None);
+    }
+
+    fn file_started(&self, path: &Path) {
+        self.record("file", Some(path));
+    }
+
+    fn site_expanded(&self, _location: &Location, generator: &str) {
+        self.record(&format!("site {}", generator), None);
+    }
+
+    fn file_written(&self, path: &Path) {
+        self.record("written", Some(path));
+    }
+
+    fn warning(&self, message: &str) {
+        self.record(&format!("warning {}", message), None);
+    }
+
+    fn error(&self, _error: &anyhow::Error) {
+        self.record("error", None);
+    }
+}
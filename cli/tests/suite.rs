@@ -1,37 +1,78 @@
-use sourcegen_cli::SourcegenParameters;
+use sourcegen_cli::testing::{assert_matches_expected, PrettyString};
+use sourcegen_cli::vfs::{FileSystem, MemoryFileSystem, RealFileSystem};
+use sourcegen_cli::{SourcegenErrorKind, SourcegenErrors, SourcegenParameters};
 use std::path::Path;
 
 pub mod generators;
 pub mod helpers;
 
+/// Environment variable that makes the test binary act as a filter tool (see `run_filter_test`).
+const FILTER_ENV: &str = "SOURCEGEN_TEST_FILTER";
+
 fn main() -> Result<(), anyhow::Error> {
+    if let Some(path) = std::env::var_os(FILTER_ENV) {
+        return run_filter(Path::new(&path));
+    }
+
     let temp = tempfile::tempdir()?;
     let root = temp.path().join("root");
     copy_dir::copy_dir("tests/test_data", &root)?;
+
+    // Run before the regular tests, as those update input directories in place
+    run_vfs_test(&root.join("003-modules"))?;
+    // Doc comments are only normalized into `///` by default
+    if !cfg!(feature = "disable_normalize_doc_attributes") {
+        run_in_memory_test(&root.join("004-doc-comments"))?;
+    }
+    run_stale_apply_test(&root.join("003-modules"))?;
+    run_plan_test(&root.join("003-modules"))?;
+    run_pipeline_errors_test(&root.join("014-pipeline"))?;
+    run_filter_test(&root.join("012-headers"))?;
+    run_testing_helpers_test(&root.join("001-strip-attributes"))?;
+
     for entry in std::fs::read_dir(&root)? {
         let entry = entry?;
         let path = entry.path();
-        if path.is_dir()
-            && path
-                .file_name()
-                .map_or(true, |name| name != "fake_sourcegen")
-        {
-            eprintln!("running test for '{}'", path.strip_prefix(&root)?.display());
+        if !path.is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name != "fake_sourcegen" {
+            eprintln!("running test for '{}'", name);
             helpers::install_rustfmt(&path)?;
-            run_test_dir(&path)?;
+        }
+        match name.as_str() {
+            "fake_sourcegen" => {}
+            "011-keep-going" => run_keep_going_test(&path)?,
+            "012-headers" => run_headers_test(&path)?,
+            "013-orphans" => run_orphans_test(&path)?,
+            "023-collect" => run_collect_test(&path)?,
+            "024-fixpoint" => run_fixpoint_test(&path)?,
+            "025-verify" => run_verify_test(&path)?,
+            // Built-in generators are only tested when their features are enabled
+            "027-json-schema"
+            | "034-json-schema-duplicate"
+            | "038-json-schema-duplicate-field"
+            | "039-json-schema-invalid-name"
+                if !cfg!(feature = "json-schema") => {}
+            "028-csv-enum" | "035-csv-enum-duplicate-value" | "036-csv-enum-empty"
+                if !cfg!(feature = "csv-enum") => {}
+            "030-embed" | "031-embed-limit" if !cfg!(feature = "embed") => {}
+            _ => run_test_dir(&path)?,
         }
     }
 
     Ok(())
 }
 
-fn parameters(manifest: &Path) -> SourcegenParameters {
+fn parameters(manifest: &Path) -> SourcegenParameters<'_> {
     SourcegenParameters {
         manifest: Some(manifest),
         generators: &[
             ("write-back", &self::generators::WriteBack),
             ("generate-impls", &self::generators::GenerateImpls),
             ("generate-simple", &self::generators::GenerateSimple),
+            ("generate-derive", &self::generators::GenerateDerive),
             (
                 "generate-doc-comments",
                 &self::generators::GenerateDocComments,
@@ -42,7 +83,29 @@ fn parameters(manifest: &Path) -> SourcegenParameters {
             ),
             ("generate-newline", &self::generators::GenerateNewLine),
             ("generate-file", &self::generators::GenerateFile),
+            ("generate-mod-files", &self::generators::GenerateModFiles),
             ("generate-trait", &self::generators::GenerateTrait),
+            ("generate-members", &self::generators::GenerateMembers),
+            ("generate-match", &self::generators::GenerateMatch),
+            ("generate-registry", &self::generators::GenerateRegistry),
+            ("generate-dispatcher", &self::generators::GenerateDispatcher),
+            ("generate-message", &self::generators::GenerateMessage),
+            ("generate-counter", &self::generators::GenerateCounter),
+            ("generate-error", &self::generators::GenerateError),
+            ("generate-name", &self::generators::GenerateName),
+            (
+                "generate-with-header",
+                &self::generators::GenerateWithHeader,
+            ),
+            #[cfg(feature = "csv-enum")]
+            ("csv-enum", &sourcegen_cli::generators::CsvEnumGenerator),
+            #[cfg(feature = "embed")]
+            ("embed", &sourcegen_cli::generators::EmbedGenerator),
+            #[cfg(feature = "json-schema")]
+            (
+                "json-schema",
+                &sourcegen_cli::generators::JsonSchemaGenerator,
+            ),
         ],
         ..Default::default()
     }
@@ -50,8 +113,546 @@ fn parameters(manifest: &Path) -> SourcegenParameters {
 
 fn run_test_dir(dir: &Path) -> Result<(), anyhow::Error> {
     let manifest = dir.join("input").join("Cargo.toml");
-    sourcegen_cli::run_sourcegen(&parameters(&manifest))?;
+    // Planned changes are compared without writing anything...
+    sourcegen_cli::testing::run_golden_dir(dir, &parameters(&manifest))?;
+    // ...and then written for real, to cover the write phase as well
+    if dir.join("expected").is_dir() {
+        sourcegen_cli::run_sourcegen(&parameters(&manifest))?;
+        assert_matches_expected(&dir.join("input"), &dir.join("expected"))?;
+    }
+    Ok(())
+}
+
+/// Expand the crate root of the test directory in memory and make sure no files were changed.
+fn run_in_memory_test(dir: &Path) -> Result<(), anyhow::Error> {
+    eprintln!(
+        "running in-memory test for '{}'",
+        dir.file_name().unwrap().to_string_lossy()
+    );
+    let path = dir.join("input").join("src").join("lib.rs");
+    let source = std::fs::read_to_string(&path)?;
+    let manifest = dir.join("input").join("Cargo.toml");
+    let output = sourcegen_cli::generate_source(&path, &source, &parameters(&manifest))?;
+
+    let expected = std::fs::read_to_string(dir.join("expected").join("src").join("lib.rs"))?;
+    pretty_assertions::assert_eq!(PrettyString(&output), PrettyString(&expected));
+    pretty_assertions::assert_eq!(
+        PrettyString(&std::fs::read_to_string(&path)?),
+        PrettyString(&source)
+    );
+    Ok(())
+}
+
+/// Filter mode of the test binary: expand the standard input into the standard output, with the
+/// same parameters as `run_headers_test`.
+fn run_filter(path: &Path) -> Result<(), anyhow::Error> {
+    let manifest = path.parent().unwrap().parent().unwrap().join("Cargo.toml");
+    let parameters = SourcegenParameters {
+        item_header: Some("Run-wide header for {generator}."),
+        ..parameters(&manifest)
+    };
+    sourcegen_cli::run_sourcegen_filter(&parameters, path)?;
+    Ok(())
+}
+
+/// Run the test binary itself as a filter tool, feeding the crate root of the test directory to
+/// its standard input, and make sure the standard output is the expected crate root.
+fn run_filter_test(dir: &Path) -> Result<(), anyhow::Error> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    eprintln!(
+        "running filter test for '{}'",
+        dir.file_name().unwrap().to_string_lossy()
+    );
+    let path = dir.join("input").join("src").join("lib.rs");
+    let source = std::fs::read_to_string(&path)?;
+    let mut child = Command::new(std::env::current_exe()?)
+        .env(FILTER_ENV, &path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(source.as_bytes())?;
+    let output = child.wait_with_output()?;
+    assert!(output.status.success(), "filter failed: {}", output.status);
+
+    let expected = std::fs::read_to_string(dir.join("expected").join("src").join("lib.rs"))?;
+    pretty_assertions::assert_eq!(
+        PrettyString(&String::from_utf8(output.stdout)?),
+        PrettyString(&expected)
+    );
+    pretty_assertions::assert_eq!(
+        PrettyString(&std::fs::read_to_string(&path)?),
+        PrettyString(&source)
+    );
+    Ok(())
+}
+
+/// Generate against unsaved files kept in memory on top of the real files, make sure nothing is
+/// written to the disk.
+fn run_vfs_test(dir: &Path) -> Result<(), anyhow::Error> {
+    eprintln!(
+        "running vfs test for '{}'",
+        dir.file_name().unwrap().to_string_lossy()
+    );
+    let input = dunce::canonicalize(dir.join("input"))?;
+    let expected = dir.join("expected");
+    let lib = input.join("src").join("lib.rs");
+    let extra = input.join("src").join("extra.rs");
+    let source = std::fs::read_to_string(&lib)?;
+
+    let fs = MemoryFileSystem::overlay(&RealFileSystem);
+    fs.insert(&lib, format!("{}\npub mod extra;\n", source));
+    fs.insert(
+        &extra,
+        "#[sourcegen::sourcegen(generator = \"generate-simple\")]\npub struct Extra;\n",
+    );
+    let manifest = input.join("Cargo.toml");
+    let mut vfs_parameters = parameters(&manifest);
+    vfs_parameters.fs = Some(&fs);
+    sourcegen_cli::run_sourcegen(&vfs_parameters)?;
+
+    let expected_lib = std::fs::read_to_string(expected.join("src").join("lib.rs"))?;
+    pretty_assertions::assert_eq!(
+        PrettyString(&fs.read_to_string(&lib)?),
+        PrettyString(&format!("{}\npub mod extra;\n", expected_lib))
+    );
+    pretty_assertions::assert_eq!(
+        PrettyString(&fs.read_to_string(&extra)?),
+        PrettyString(
+            "#[sourcegen::sourcegen(generator = \"generate-simple\")]\n\
+             // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.\n\
+             pub struct Extra {\n    \
+                 pub hello: String,\n\
+             }\n"
+        )
+    );
+    let two = Path::new("src").join("two").join("mod.rs");
+    pretty_assertions::assert_eq!(
+        PrettyString(&fs.read_to_string(&input.join(&two))?),
+        PrettyString(&std::fs::read_to_string(expected.join(&two))?)
+    );
+
+    // Changes planned against the in-memory files are applied to them as well
+    let plan_fs = MemoryFileSystem::overlay(&RealFileSystem);
+    plan_fs.insert(&lib, format!("{}\npub mod extra;\n", source));
+    plan_fs.insert(
+        &extra,
+        "#[sourcegen::sourcegen(generator = \"generate-simple\")]\npub struct Extra;\n",
+    );
+    let plan_parameters = SourcegenParameters {
+        fs: Some(&plan_fs),
+        ..parameters(&manifest)
+    };
+    let changes = sourcegen_cli::plan_sourcegen(&plan_parameters)?;
+    sourcegen_cli::apply_changes_with(&plan_fs, &changes)?;
+    assert_eq!(plan_fs.files(), fs.files());
+
+    // The report goes through the file system of the run, too
+    let report = input.join("report.json");
+    let report_parameters = SourcegenParameters {
+        fs: Some(&fs),
+        report: Some(&report),
+        ..parameters(&manifest)
+    };
+    sourcegen_cli::run_sourcegen(&report_parameters)?;
+    let json: serde_json::Value = serde_json::from_str(&fs.get(&report).unwrap())?;
+    assert_eq!(json["packages"], serde_json::json!(["test"]));
+
+    // Nothing is written to the disk
+    assert_eq!(std::fs::read_to_string(&lib)?, source);
+    assert!(!extra.exists());
+    assert!(!report.exists());
+    assert_ne!(
+        std::fs::read_to_string(input.join(&two))?,
+        std::fs::read_to_string(expected.join(&two))?
+    );
+    Ok(())
+}
+
+/// Check the string helpers of `sourcegen_cli::testing`.
+fn run_testing_helpers_test(dir: &Path) -> Result<(), anyhow::Error> {
+    eprintln!("running testing helpers test");
+    let manifest = dir.join("input").join("Cargo.toml");
+    let generators = parameters(&manifest).generators;
+    sourcegen_cli::testing::assert_expands_to(
+        "#[sourcegen::sourcegen(generator = \"generate-simple\")]\n\
+         pub struct Simple;\n",
+        generators,
+        "#[sourcegen::sourcegen(generator = \"generate-simple\")]\n\
+         // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.\n\
+         pub struct Simple {\n    \
+             pub hello: String,\n\
+         }\n",
+    );
+
+    let err = sourcegen_cli::testing::expand_error(
+        "#[sourcegen::sourcegen(generator = \"generate-error\")]\n\
+         pub struct Failing;\n",
+        generators,
+    );
+    assert!(
+        matches!(
+            err.downcast_ref::<SourcegenErrorKind>(),
+            Some(SourcegenErrorKind::GeneratorError(_))
+        ),
+        "unexpected error: {:#}",
+        err
+    );
+    Ok(())
+}
+
+/// Check that ambiguous combinations of stacked generators are rejected.
+fn run_pipeline_errors_test(dir: &Path) -> Result<(), anyhow::Error> {
+    eprintln!(
+        "running pipeline errors test for '{}'",
+        dir.file_name().unwrap().to_string_lossy()
+    );
+    let path = dir.join("input").join("src").join("lib.rs");
+    let manifest = dir.join("input").join("Cargo.toml");
+    let parameters = parameters(&manifest);
+    let cases = [
+        "#[sourcegen::sourcegen(generator = \"generate-simple\")]\n\
+         #[derive(Clone)]\n\
+         #[sourcegen::sourcegen(generator = \"generate-derive\")]\n\
+         struct Separated;\n",
+        "#[sourcegen::sourcegen(generator = \"generate-simple\", header = \"One\")]\n\
+         #[sourcegen::sourcegen(generator = \"generate-derive\", header = \"Two\")]\n\
+         struct TwoHeaders;\n",
+        "#[sourcegen::sourcegen(generator = \"generate-file\", file = true)]\n\
+         #[sourcegen::sourcegen(generator = \"generate-derive\")]\n\
+         struct WholeFile;\n",
+        "mod inline {\n\
+             #![sourcegen::sourcegen(generator = \"generate-file\")]\n\
+             #![sourcegen::sourcegen(generator = \"generate-derive\")]\n\
+         }\n",
+    ];
+    for source in &cases {
+        let err = sourcegen_cli::generate_source(&path, source, &parameters).unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<SourcegenErrorKind>(),
+                Some(SourcegenErrorKind::InvalidPipeline(_, _))
+            ),
+            "unexpected error: {:#}",
+            err
+        );
+    }
+    Ok(())
+}
+
+/// Plan changes for the test directory, verify that nothing is written until changes are applied.
+fn run_plan_test(dir: &Path) -> Result<(), anyhow::Error> {
+    eprintln!(
+        "running plan test for '{}'",
+        dir.file_name().unwrap().to_string_lossy()
+    );
+    let input = dir.join("input");
+    let expected = dir.join("expected");
+    let changes = sourcegen_cli::plan_sourcegen(&parameters(&input.join("Cargo.toml")))?;
+    assert!(!changes.is_empty(), "expected some changes to be planned");
+
+    for change in &changes {
+        let relative = change.path.strip_prefix(&input)?;
+        assert_eq!(std::fs::read_to_string(&change.path)?, change.old);
+        pretty_assertions::assert_eq!(
+            PrettyString(&change.new),
+            PrettyString(&std::fs::read_to_string(expected.join(relative))?)
+        );
+        assert!(!change.regions.is_empty());
+        for region in &change.regions {
+            assert_eq!(region.generator, "generate-simple");
+            assert_eq!(region.location.path(), change.path);
+            assert!(change.old[region.range.clone()].contains("pub struct TestStruct;"));
+        }
+    }
+
+    sourcegen_cli::apply_changes(&changes)?;
+    assert_matches_expected(&input, &expected)?;
+    Ok(())
+}
+
+/// Modify one of the files after changes were planned and make sure applying them fails without
+/// writing any of the files.
+fn run_stale_apply_test(dir: &Path) -> Result<(), anyhow::Error> {
+    eprintln!(
+        "running stale apply test for '{}'",
+        dir.file_name().unwrap().to_string_lossy()
+    );
+    let input = dir.join("input");
+    let changes = sourcegen_cli::plan_sourcegen(&parameters(&input.join("Cargo.toml")))?;
+    let modified = &changes.last().unwrap().path;
+    std::fs::write(
+        modified,
+        format!("{}// modified\n", changes.last().unwrap().old),
+    )?;
+
+    let err = sourcegen_cli::apply_changes(&changes).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<SourcegenErrorKind>(),
+        Some(&SourcegenErrorKind::FileModified(
+            modified.display().to_string()
+        ))
+    );
+    for change in &changes[..changes.len() - 1] {
+        assert_eq!(std::fs::read_to_string(&change.path)?, change.old);
+    }
+
+    // Restore the original content for the next tests
+    std::fs::write(modified, &changes.last().unwrap().old)?;
+    Ok(())
+}
+
+/// Run with `keep_going` set, verify that successful sites are written and all errors reported.
+fn run_keep_going_test(dir: &Path) -> Result<(), anyhow::Error> {
+    let manifest = dir.join("input").join("Cargo.toml");
+    let report = dir.join("report.json");
+    let observer = self::helpers::RecordingObserver::default();
+    let parameters = SourcegenParameters {
+        keep_going: true,
+        report: Some(&report),
+        observer: Some(&observer),
+        ..parameters(&manifest)
+    };
+    let err = sourcegen_cli::run_sourcegen(&parameters).unwrap_err();
+    let errors = err
+        .downcast_ref::<SourcegenErrors>()
+        .expect("expected an aggregated error")
+        .errors()
+        .iter()
+        .map(|err| err.downcast_ref::<SourcegenErrorKind>().cloned())
+        .collect::<Vec<_>>();
+    assert_eq!(errors.len(), 3, "unexpected errors: {}", err);
+    assert!(matches!(
+        errors[0],
+        Some(SourcegenErrorKind::GeneratorNotFound(_, ref name)) if name == "unknown"
+    ));
+    assert!(matches!(
+        errors[1],
+        Some(SourcegenErrorKind::GeneratorError(_))
+    ));
+    assert!(matches!(
+        errors[2],
+        Some(SourcegenErrorKind::ProcessFile(ref path)) if path.ends_with("broken.rs")
+    ));
+
+    let events = observer.events.into_inner();
+    assert_eq!(
+        events,
+        vec![
+            "package test",
+            "file lib.rs",
+            "site generate-simple",
+            "error",
+            "error",
+            "site generate-simple",
+            "file broken.rs",
+            "error",
+            "file other.rs",
+            "site generate-simple",
+            "written lib.rs",
+            "written other.rs",
+        ]
+    );
+
+    let report: serde_json::Value = serde_json::from_reader(std::fs::File::open(&report)?)?;
+    let sites = report["sites"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|site| {
+            (
+                site["generator"].as_str().unwrap_or_default(),
+                site["status"].as_str().unwrap(),
+                site["error"]["kind"].as_str().unwrap_or_default(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sites,
+        vec![
+            ("generate-simple", "updated", ""),
+            ("", "failed", "GeneratorNotFound"),
+            ("generate-error", "failed", "GeneratorError"),
+            ("generate-simple", "updated", ""),
+            ("generate-simple", "updated", ""),
+        ]
+    );
+    assert_eq!(report["errors"].as_array().unwrap().len(), 3);
+    assert_eq!(report["generators"]["generate-simple"]["calls"], 3);
+    assert_eq!(report["rustfmt"].as_array().unwrap().len(), 3);
+
+    assert_matches_expected(&dir.join("input"), &dir.join("expected"))?;
+    Ok(())
+}
+
+/// Run with a custom header template for the whole run.
+fn run_headers_test(dir: &Path) -> Result<(), anyhow::Error> {
+    let manifest = dir.join("input").join("Cargo.toml");
+    let parameters = SourcegenParameters {
+        item_header: Some("Run-wide header for {generator}."),
+        ..parameters(&manifest)
+    };
+    sourcegen_cli::run_sourcegen(&parameters)?;
+    assert_matches_expected(&dir.join("input"), &dir.join("expected"))?;
+    Ok(())
+}
+
+/// Run with removal of orphaned generated items and headers.
+fn run_orphans_test(dir: &Path) -> Result<(), anyhow::Error> {
+    let manifest = dir.join("input").join("Cargo.toml");
+    let observer = self::helpers::RecordingObserver::default();
+    let parameters = SourcegenParameters {
+        remove_orphans: true,
+        observer: Some(&observer),
+        ..parameters(&manifest)
+    };
+    sourcegen_cli::run_sourcegen(&parameters)?;
 
-    self::helpers::assert_matches_expected(dir, &dir.join("input"), &dir.join("expected"))?;
+    let warnings = observer
+        .events
+        .into_inner()
+        .into_iter()
+        .filter(|event| event.starts_with("warning"))
+        .collect::<Vec<_>>();
+    assert_eq!(warnings.len(), 3);
+    assert!(warnings[0].contains("line: 2") && warnings[0].contains("generated item"));
+    assert!(warnings[1].contains("line: 17") && warnings[1].contains("generated item"));
+    assert!(warnings[2].contains("line: 5") && warnings[2].contains("header comment"));
+
+    assert_matches_expected(&dir.join("input"), &dir.join("expected"))?;
+    Ok(())
+}
+
+/// Collect phase runs on every iteration in the fixpoint mode, sites must not be collected twice.
+fn run_collect_test(dir: &Path) -> Result<(), anyhow::Error> {
+    let manifest = dir.join("input").join("Cargo.toml");
+    let fixpoint_parameters = SourcegenParameters {
+        fixpoint: Some(3),
+        ..parameters(&manifest)
+    };
+    sourcegen_cli::run_sourcegen(&fixpoint_parameters)?;
+    assert_matches_expected(&dir.join("input"), &dir.join("expected"))?;
+    Ok(())
+}
+
+/// Run until nothing changes: the registry depends on the output of the other generator.
+fn run_fixpoint_test(dir: &Path) -> Result<(), anyhow::Error> {
+    let manifest = dir.join("input").join("Cargo.toml");
+
+    // At least one iteration is required, nothing is written otherwise
+    let zero_parameters = SourcegenParameters {
+        fixpoint: Some(0),
+        ..parameters(&manifest)
+    };
+    let err = sourcegen_cli::run_sourcegen(&zero_parameters).unwrap_err();
+    match err.downcast_ref::<SourcegenErrorKind>() {
+        Some(SourcegenErrorKind::InvalidFixpoint) => {}
+        _ => panic!("unexpected error: {:#}", err),
+    }
+
+    let report = dir.join("report.json");
+    let fixpoint_parameters = SourcegenParameters {
+        fixpoint: Some(5),
+        report: Some(&report),
+        ..parameters(&manifest)
+    };
+    sourcegen_cli::run_sourcegen(&fixpoint_parameters)?;
+    assert_matches_expected(&dir.join("input"), &dir.join("expected"))?;
+
+    // Every site is reported once per iteration
+    let report: serde_json::Value = serde_json::from_reader(std::fs::File::open(&report)?)?;
+    let iterations = report["iterations"].as_u64().unwrap();
+    assert_eq!(iterations, 3);
+    for iteration in 1..=iterations {
+        let sites = report["sites"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|site| site["iteration"] == iteration)
+            .count();
+        assert_eq!(sites, 2, "iteration {}", iteration);
+    }
+    assert_eq!(report["packages"].as_array().unwrap().len(), 1);
+
+    // Generator changing its output on every run never converges
+    let manifest = dir.join("churn").join("Cargo.toml");
+    let churn_parameters = SourcegenParameters {
+        fixpoint: Some(3),
+        ..parameters(&manifest)
+    };
+    let err = sourcegen_cli::run_sourcegen(&churn_parameters).unwrap_err();
+    match err.downcast_ref::<SourcegenErrorKind>() {
+        Some(SourcegenErrorKind::NotConverged(3, sites)) => {
+            assert!(sites.contains("lib.rs (line: 1") && sites.contains("generate-counter"));
+            assert!(
+                !sites.contains("generate-message"),
+                "unexpected sites: {}",
+                sites
+            );
+        }
+        _ => panic!("unexpected error: {:#}", err),
+    }
+    Ok(())
+}
+
+/// Verify that generators are idempotent before writing anything.
+fn run_verify_test(dir: &Path) -> Result<(), anyhow::Error> {
+    let manifest = dir.join("input").join("Cargo.toml");
+    let verify_parameters = SourcegenParameters {
+        verify: true,
+        ..parameters(&manifest)
+    };
+    sourcegen_cli::run_sourcegen(&verify_parameters)?;
+    assert_matches_expected(&dir.join("input"), &dir.join("expected"))?;
+
+    let path = dir.join("input").join("src").join("generated.rs");
+    let fs = sourcegen_cli::vfs::RealFileSystem;
+    sourcegen_cli::verify_single_file(&fs, &path, "generate-struct", |_source| {
+        Ok(quote::quote!(
+            pub struct Generated;
+        ))
+    })?;
+    assert!(!path.exists());
+
+    // Generator output depends on the current content of the file
+    let err = sourcegen_cli::verify_single_file(&fs, &path, "generate-struct", |source| {
+        let name = quote::format_ident!("Generated{}", source.len());
+        Ok(quote::quote!(
+            pub struct #name;
+        ))
+    })
+    .unwrap_err();
+    match err.downcast_ref::<SourcegenErrorKind>() {
+        Some(SourcegenErrorKind::NotIdempotent(sites)) => {
+            assert!(sites.contains("generated.rs (whole file) (generate-struct)"))
+        }
+        _ => panic!("unexpected error: {:#}", err),
+    }
+    assert!(!path.exists());
+
+    // Generator changing its output on every run is reported, nothing is written
+    let manifest = dir.join("churn").join("Cargo.toml");
+    let source = std::fs::read_to_string(dir.join("churn").join("src").join("lib.rs"))?;
+    let churn_parameters = SourcegenParameters {
+        verify: true,
+        ..parameters(&manifest)
+    };
+    let err = sourcegen_cli::run_sourcegen(&churn_parameters).unwrap_err();
+    match err.downcast_ref::<SourcegenErrorKind>() {
+        Some(SourcegenErrorKind::NotIdempotent(sites)) => {
+            assert!(sites.contains("lib.rs (line: 4") && sites.contains("generate-counter"));
+            assert!(
+                !sites.contains("generate-message"),
+                "unexpected sites: {}",
+                sites
+            );
+        }
+        _ => panic!("unexpected error: {:#}", err),
+    }
+    assert_eq!(
+        std::fs::read_to_string(dir.join("churn").join("src").join("lib.rs"))?,
+        source
+    );
     Ok(())
 }
@@ -0,0 +1,2 @@
+#[sourcegen::sourcegen(generator = "generate-simple")]
+struct Broken
@@ -0,0 +1,22 @@
+#[sourcegen::sourcegen(generator = "generate-simple")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+struct First {
+    pub hello: String,
+}
+
+#[sourcegen::sourcegen(generator = "unknown")]
+struct Unknown;
+
+#[sourcegen::sourcegen(generator = "generate-error")]
+struct Failing;
+#[sourcegen::generated]
+impl Failing {}
+
+#[sourcegen::sourcegen(generator = "generate-simple")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+struct Last {
+    pub hello: String,
+}
+
+mod broken;
+mod other;
@@ -0,0 +1,5 @@
+#[sourcegen::sourcegen(generator = "generate-simple")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+struct Other {
+    pub hello: String,
+}
@@ -0,0 +1,16 @@
+#[sourcegen::sourcegen(generator = "generate-simple")]
+struct First;
+
+#[sourcegen::sourcegen(generator = "unknown")]
+struct Unknown;
+
+#[sourcegen::sourcegen(generator = "generate-error")]
+struct Failing;
+#[sourcegen::generated]
+impl Failing {}
+
+#[sourcegen::sourcegen(generator = "generate-simple")]
+struct Last;
+
+mod broken;
+mod other;
@@ -0,0 +1,27 @@
+#[sourcegen::sourcegen(generator = "generate-simple")]
+// Run-wide header for generate-simple.
+struct RunHeader {
+    pub hello: String,
+}
+
+#[sourcegen::sourcegen(generator = "generate-with-header")]
+// Generated by generate-with-header, do not edit.
+struct GeneratorHeader {
+    pub hello: String,
+}
+
+#[sourcegen::sourcegen(
+    generator = "generate-simple",
+    schema = "widget.json",
+    header = "Generated from {schema} by {generator}.\nSee {{schema}} docs."
+)]
+// Generated from widget.json by generate-simple.
+// See {schema} docs.
+struct SiteHeader {
+    pub hello: String,
+}
+
+#[sourcegen::sourcegen(generator = "generate-with-header", header = false)]
+struct NoHeader {
+    pub hello: String,
+}
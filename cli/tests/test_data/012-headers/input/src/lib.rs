@@ -0,0 +1,18 @@
+#[sourcegen::sourcegen(generator = "generate-simple")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+struct RunHeader;
+
+#[sourcegen::sourcegen(generator = "generate-with-header")]
+struct GeneratorHeader;
+
+#[sourcegen::sourcegen(
+    generator = "generate-simple",
+    schema = "widget.json",
+    header = "Generated from {schema} by {generator}.\nSee {{schema}} docs."
+)]
+// Old custom header
+struct SiteHeader;
+
+#[sourcegen::sourcegen(generator = "generate-with-header", header = false)]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+struct NoHeader;
@@ -0,0 +1,19 @@
+struct Kept;
+
+struct Stray;
+
+#[sourcegen::sourcegen(generator = "generate-impls")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+struct Live;
+#[sourcegen::generated]
+impl Live {}
+
+mod nested {
+    struct Inner;
+}
+
+// Header inside a literal is a part of the program, not a stray comment
+const TEMPLATE: &str = r#"
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+struct Template;
+"#;
@@ -0,0 +1,15 @@
+#[sourcegen::sourcegen(generator = "generate-simple")]
+#[sourcegen::sourcegen(generator = "generate-derive")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+#[derive(Debug)]
+struct Simple {
+    pub hello: String,
+}
+
+#[sourcegen::sourcegen(generator = "generate-impls")]
+#[sourcegen::sourcegen(generator = "generate-derive", header = "Generated by {generator}.")]
+// Generated by generate-impls -> generate-derive.
+#[derive(Debug)]
+struct WithImpls;
+#[sourcegen::generated]
+impl WithImpls {}
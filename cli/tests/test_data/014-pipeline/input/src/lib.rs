@@ -0,0 +1,10 @@
+#[sourcegen::sourcegen(generator = "generate-simple")]
+#[sourcegen::sourcegen(generator = "generate-derive")]
+struct Simple;
+
+#[sourcegen::sourcegen(generator = "generate-impls")]
+#[sourcegen::sourcegen(generator = "generate-derive", header = "Generated by {generator}.")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+struct WithImpls;
+#[sourcegen::generated]
+impl WithImpls {}
@@ -0,0 +1,4 @@
+// Generated. All manual edits below this line will be discarded.
+struct Hello;
+#[sourcegen::generated]
+impl Hello {}
@@ -0,0 +1,2 @@
+// Generated. All manual edits below this line will be discarded.
+struct Inner;
@@ -0,0 +1,15 @@
+#[sourcegen::sourcegen(generator = "generate-impls", output = "generated/hello.rs")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+struct Hello;
+#[sourcegen::generated]
+include!("generated/hello.rs");
+
+#[sourcegen::sourcegen(generator = "write-back", output = "generated/nested.rs")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+pub mod nested {
+    struct Inner;
+}
+#[sourcegen::generated]
+pub mod nested {
+    include!("generated/nested.rs");
+}
@@ -0,0 +1,10 @@
+#[sourcegen::sourcegen(generator = "generate-mod-files", file = true)]
+struct __Unused;
+
+// Handwritten content is kept
+pub mod widget;
+
+pub fn helper() {}
+
+pub mod extra;
+pub mod gadget;
@@ -0,0 +1,2 @@
+// Generated. All manual edits below this line will be discarded.
+pub mod deep;
@@ -0,0 +1,3 @@
+// Generated. All manual edits below this line will be discarded.
+pub struct Gadget;
+pub mod part;
@@ -0,0 +1,7 @@
+#[sourcegen::sourcegen(generator = "generate-mod-files", file = true)]
+struct __Unused;
+
+// Handwritten content is kept
+pub mod widget;
+
+pub fn helper() {}
@@ -0,0 +1,31 @@
+pub struct Point {
+    x: u32,
+    y: u32,
+}
+
+impl Point {
+    pub fn new(x: u32, y: u32) -> Self {
+        Point { x, y }
+    }
+
+    #[sourcegen::sourcegen(generator = "generate-members")]
+    fn __accessors() {}
+    #[sourcegen::generated]
+    pub fn z(&self) -> u32 {
+        0
+    }
+
+    pub fn manual(&self) -> u32 {
+        self.x + self.y
+    }
+}
+
+mod nested {
+    pub trait Shape {
+        fn area(&self) -> f64;
+
+        #[sourcegen::sourcegen(generator = "generate-members")]
+        // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+        fn describe(&self) -> String;
+    }
+}
@@ -0,0 +1,38 @@
+pub fn lookup() -> usize {
+    #[sourcegen::sourcegen(generator = "generate-simple")]
+    // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+    struct Local {
+        pub hello: String,
+    }
+
+    let value = Local {
+        hello: String::new(),
+    };
+    value.hello.len()
+}
+
+pub struct Foo;
+
+impl Foo {
+    pub fn method(&self) {
+        #[sourcegen::sourcegen(generator = "generate-simple")]
+        // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+        struct InMethod {
+            pub hello: String,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_it() {
+        #[sourcegen::sourcegen(generator = "generate-impls")]
+        // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+        struct Helper;
+        #[sourcegen::generated]
+        impl Helper {}
+
+        assert!(true);
+    }
+}
@@ -0,0 +1,34 @@
+pub fn lookup() -> usize {
+    #[sourcegen::sourcegen(generator = "generate-simple")]
+    struct Local;
+
+    let value = Local {
+        hello: String::new(),
+    };
+    value.hello.len()
+}
+
+pub struct Foo;
+
+impl Foo {
+    pub fn method(&self) {
+        #[sourcegen::sourcegen(generator = "generate-simple")]
+        // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+        struct InMethod;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_it() {
+        #[sourcegen::sourcegen(generator = "generate-impls")]
+        struct Helper;
+        #[sourcegen::generated]
+        impl Helper {
+            fn old() {}
+        }
+
+        assert!(true);
+    }
+}
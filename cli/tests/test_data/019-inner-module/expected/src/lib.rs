@@ -0,0 +1,12 @@
+pub struct Before;
+
+mod generated {
+    #![sourcegen::sourcegen(generator = "generate-file")]
+    // Generated. All manual edits below this line will be discarded.
+    /// Some generated comment here
+    struct Hello {
+        pub hello: String,
+    }
+}
+
+pub struct After;
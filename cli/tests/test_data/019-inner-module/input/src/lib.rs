@@ -0,0 +1,10 @@
+pub struct Before;
+
+mod generated {
+    #![sourcegen::sourcegen(generator = "generate-file")]
+    #![allow(dead_code)]
+    // will be discarded
+    pub enum Test {}
+}
+
+pub struct After;
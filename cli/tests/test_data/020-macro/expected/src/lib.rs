@@ -0,0 +1,35 @@
+pub struct Before;
+
+sourcegen::generate! { generator = "generate-file" }
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+#[sourcegen::generated]
+/// Some generated comment here
+struct Hello {
+    pub hello: String,
+}
+
+pub struct After;
+
+mod nested {
+    sourcegen::generate!(generator = "generate-file");
+    // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+    #[sourcegen::generated]
+    /// Some generated comment here
+    struct Hello {
+        pub hello: String,
+    }
+
+    pub struct Handwritten;
+}
+
+fn body() {
+    sourcegen::generate! { generator = "generate-file" }
+    // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+    #[sourcegen::generated]
+    /// Some generated comment here
+    struct Hello {
+        pub hello: String,
+    }
+
+    let _x = 1;
+}
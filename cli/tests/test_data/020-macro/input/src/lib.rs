@@ -0,0 +1,20 @@
+pub struct Before;
+
+sourcegen::generate! { generator = "generate-file" }
+
+pub struct After;
+
+mod nested {
+    sourcegen::generate!(generator = "generate-file");
+    // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+    #[sourcegen::generated]
+    struct Stale;
+
+    pub struct Handwritten;
+}
+
+fn body() {
+    sourcegen::generate! { generator = "generate-file" }
+
+    let _x = 1;
+}
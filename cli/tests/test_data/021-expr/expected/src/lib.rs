@@ -0,0 +1,55 @@
+pub fn lookup(key: &str) -> Option<u32> {
+    let result = sourcegen::expr!(generator = "generate-match", keys = "a, b";
+        // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+        match key {
+            "a" => Some(0u32),
+            "b" => Some(1u32),
+            _ => None,
+        }
+    );
+    result
+}
+
+pub struct Widget;
+
+impl Widget {
+    pub fn index(&self, name: &str) -> Option<u32> {
+        if name.is_empty() {
+            return None;
+        }
+        sourcegen::expr!(generator = "generate-match", keys = "x, y, z";
+            // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+            match name {
+                "x" => Some(0u32),
+                "y" => Some(1u32),
+                "z" => Some(2u32),
+                _ => None,
+            }
+        )
+    }
+}
+
+pub fn outer() {
+    fn inner(value: &str) -> Option<u32> {
+        sourcegen::expr!(generator = "generate-match", keys = "one";
+            // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+            match value {
+                "one" => Some(0u32),
+                _ => None,
+            }
+        )
+    }
+    let _ = inner("one");
+}
+
+// Two-space indent, non-ASCII text in front of the marker
+pub fn accent(key: &str) -> (&'static str, Option<u32>) {
+  ("é", sourcegen::expr!(generator = "generate-match", keys = "ä, ö";
+    // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+    match key {
+        "ä" => Some(0u32),
+        "ö" => Some(1u32),
+        _ => None,
+    }
+  ))
+}
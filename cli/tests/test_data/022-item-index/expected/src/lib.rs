@@ -0,0 +1,14 @@
+mod messages;
+
+pub struct LoginMessage;
+
+pub mod registry {
+    #[sourcegen::sourcegen(generator = "generate-registry")]
+    // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+    /// Messages, registered from `crate::registry`
+    pub enum Message {
+        LoginMessage(crate::LoginMessage),
+        LogoutMessage(crate::messages::LogoutMessage),
+        PingMessage(crate::messages::nested::PingMessage),
+    }
+}
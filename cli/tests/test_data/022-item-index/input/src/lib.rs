@@ -0,0 +1,8 @@
+mod messages;
+
+pub struct LoginMessage;
+
+pub mod registry {
+    #[sourcegen::sourcegen(generator = "generate-registry")]
+    pub enum Message {}
+}
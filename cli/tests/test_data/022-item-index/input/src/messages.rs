@@ -0,0 +1,7 @@
+pub struct LogoutMessage;
+
+pub struct Unrelated;
+
+pub mod nested {
+    pub struct PingMessage;
+}
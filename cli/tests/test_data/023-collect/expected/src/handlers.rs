@@ -0,0 +1,7 @@
+#[sourcegen::sourcegen(generator = "generate-dispatcher", role = "handler")]
+pub struct Logout;
+
+pub mod admin {
+    #[sourcegen::sourcegen(generator = "generate-dispatcher", role = "handler")]
+    pub struct Ban;
+}
@@ -0,0 +1,12 @@
+mod handlers;
+
+#[sourcegen::sourcegen(generator = "generate-dispatcher", role = "dispatcher")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+pub enum Handler {
+    Login(crate::Login),
+    Logout(crate::handlers::Logout),
+    Ban(crate::handlers::admin::Ban),
+}
+
+#[sourcegen::sourcegen(generator = "generate-dispatcher", role = "handler")]
+pub struct Login;
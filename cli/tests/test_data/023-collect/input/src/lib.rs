@@ -0,0 +1,7 @@
+mod handlers;
+
+#[sourcegen::sourcegen(generator = "generate-dispatcher", role = "dispatcher")]
+pub enum Handler {}
+
+#[sourcegen::sourcegen(generator = "generate-dispatcher", role = "handler")]
+pub struct Login;
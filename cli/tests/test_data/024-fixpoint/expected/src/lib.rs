@@ -0,0 +1,14 @@
+pub mod registry {
+    #[sourcegen::sourcegen(generator = "generate-registry")]
+    // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+    /// Messages, registered from `crate::registry`
+    pub enum Message {
+        PingMessage(crate::PingMessage),
+    }
+}
+
+#[sourcegen::sourcegen(generator = "generate-message")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+pub struct Ping;
+#[sourcegen::generated]
+pub struct PingMessage;
@@ -0,0 +1,7 @@
+pub mod registry {
+    #[sourcegen::sourcegen(generator = "generate-registry")]
+    pub enum Message {}
+}
+
+#[sourcegen::sourcegen(generator = "generate-message")]
+pub struct Ping;
@@ -0,0 +1,5 @@
+#[sourcegen::sourcegen(generator = "generate-message")]
+pub struct Ping;
+
+#[sourcegen::sourcegen(generator = "generate-counter")]
+pub struct Counter;
@@ -0,0 +1,5 @@
+#[sourcegen::sourcegen(generator = "generate-message")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+pub struct Ping;
+#[sourcegen::generated]
+pub struct PingMessage;
@@ -0,0 +1,2 @@
+#[sourcegen::sourcegen(generator = "generate-message")]
+pub struct Ping;
@@ -0,0 +1,2 @@
+#[sourcegen::sourcegen(generator = "generate-error")]
+pub struct Failing;
@@ -0,0 +1,193 @@
+#[sourcegen::sourcegen(generator = "json-schema", schema = "widget.json")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+/// Widget on the dashboard
+#[derive(Debug, Clone, PartialEq)]
+pub struct Widget {
+    /// Nested widgets
+    pub children: Option<Vec<Widget>>,
+    /// Color of the widget
+    pub color: Option<Color>,
+    /// Name shown to the user, if different
+    pub display_name: Option<String>,
+    /// Name of the widget
+    pub name: String,
+    /// Widget this one is attached to
+    pub parent: Option<Box<Widget>>,
+    /// Position on the screen
+    pub position: Option<WidgetPosition>,
+    /// Shape of the widget
+    pub shape: Shape,
+    pub status: Option<Status>,
+    pub tags: Option<Vec<String>>,
+    /// Weight of the widget
+    pub weight: u32,
+}
+#[sourcegen::generated]
+/// Color of the widget
+#[derive(Debug, Clone, PartialEq)]
+pub enum Color {
+    Red,
+    Green,
+    DarkBlue,
+}
+#[sourcegen::generated]
+/// Position on the screen
+#[derive(Debug, Clone, PartialEq)]
+pub struct WidgetPosition {
+    pub x: f64,
+    pub y: f64,
+}
+#[sourcegen::generated]
+/// Shape of the widget
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Circle(Circle),
+    Rectangle(Rectangle),
+    /// Shape given by its name
+    Named(String),
+}
+#[sourcegen::generated]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    /// Widget is shown
+    Active,
+    /// Widget is hidden
+    Hidden,
+}
+#[sourcegen::generated]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Circle {
+    pub radius: f32,
+}
+#[sourcegen::generated]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rectangle {
+    pub height: f64,
+    pub width: f64,
+}
+
+mod serialized {
+    use serde::{Deserialize, Serialize};
+
+    #[sourcegen::sourcegen(
+        generator = "json-schema",
+        schema = "widget.json",
+        definition = "shape",
+        derive = "Debug, Serialize, Deserialize"
+    )]
+    // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+    /// Shape of the widget
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(untagged)]
+    pub enum Shape {
+        Circle(Circle),
+        Rectangle(Rectangle),
+        /// Shape given by its name
+        Named(String),
+    }
+    #[sourcegen::generated]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct Circle {
+        pub radius: f32,
+    }
+    #[sourcegen::generated]
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct Rectangle {
+        pub height: f64,
+        pub width: f64,
+    }
+}
+
+pub mod status {
+    use serde::{Deserialize, Serialize};
+
+    #[sourcegen::sourcegen(
+        generator = "json-schema",
+        schema = "widget.json",
+        definition = "status",
+        derive = "Serialize, Deserialize"
+    )]
+    // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+    #[derive(Serialize, Deserialize)]
+    pub enum Status {
+        /// Widget is shown
+        #[serde(rename = "active")]
+        Active,
+        /// Widget is hidden
+        #[serde(rename = "hidden")]
+        Hidden,
+    }
+}
+
+pub mod shared {
+    #[sourcegen::sourcegen(generator = "json-schema", schema = "widget.json", definition = "shape")]
+    // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+    /// Shape of the widget
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Shape {
+        Circle(Circle),
+        Rectangle(Rectangle),
+        /// Shape given by its name
+        Named(String),
+    }
+    #[sourcegen::generated]
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Circle {
+        pub radius: f32,
+    }
+    #[sourcegen::generated]
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Rectangle {
+        pub height: f64,
+        pub width: f64,
+    }
+
+    // Shapes are already generated above
+    #[sourcegen::sourcegen(generator = "json-schema", schema = "widget.json")]
+    // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+    /// Widget on the dashboard
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Widget {
+        /// Nested widgets
+        pub children: Option<Vec<Widget>>,
+        /// Color of the widget
+        pub color: Option<Color>,
+        /// Name shown to the user, if different
+        pub display_name: Option<String>,
+        /// Name of the widget
+        pub name: String,
+        /// Widget this one is attached to
+        pub parent: Option<Box<Widget>>,
+        /// Position on the screen
+        pub position: Option<WidgetPosition>,
+        /// Shape of the widget
+        pub shape: Shape,
+        pub status: Option<Status>,
+        pub tags: Option<Vec<String>>,
+        /// Weight of the widget
+        pub weight: u32,
+    }
+    #[sourcegen::generated]
+    /// Color of the widget
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Color {
+        Red,
+        Green,
+        DarkBlue,
+    }
+    #[sourcegen::generated]
+    /// Position on the screen
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct WidgetPosition {
+        pub x: f64,
+        pub y: f64,
+    }
+    #[sourcegen::generated]
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Status {
+        /// Widget is shown
+        Active,
+        /// Widget is hidden
+        Hidden,
+    }
+}
@@ -0,0 +1,35 @@
+#[sourcegen::sourcegen(generator = "json-schema", schema = "widget.json")]
+pub struct Widget;
+
+mod serialized {
+    use serde::{Deserialize, Serialize};
+
+    #[sourcegen::sourcegen(
+        generator = "json-schema",
+        schema = "widget.json",
+        definition = "shape",
+        derive = "Debug, Serialize, Deserialize"
+    )]
+    pub enum Shape {}
+}
+
+pub mod status {
+    use serde::{Deserialize, Serialize};
+
+    #[sourcegen::sourcegen(
+        generator = "json-schema",
+        schema = "widget.json",
+        definition = "status",
+        derive = "Serialize, Deserialize"
+    )]
+    pub enum Status {}
+}
+
+pub mod shared {
+    #[sourcegen::sourcegen(generator = "json-schema", schema = "widget.json", definition = "shape")]
+    pub enum Shape {}
+
+    // Shapes are already generated above
+    #[sourcegen::sourcegen(generator = "json-schema", schema = "widget.json")]
+    pub struct Widget;
+}
@@ -0,0 +1,93 @@
+#[sourcegen::sourcegen(
+    generator = "csv-enum",
+    table = "errors.csv",
+    value = "code",
+    doc = "description"
+)]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum ErrorCode {
+    /// Resource does not exist
+    NotFound = 404,
+    /// Client sent too many requests, retry later
+    TooManyRequests = 429,
+    Internal = 500,
+}
+#[sourcegen::generated]
+impl ErrorCode {
+    /// Name of the variant as it is in the `name` column.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::NotFound => "not_found",
+            ErrorCode::TooManyRequests => "too_many_requests",
+            ErrorCode::Internal => "internal",
+        }
+    }
+}
+#[sourcegen::generated]
+impl std::str::FromStr for ErrorCode {
+    type Err = String;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "not_found" => Ok(ErrorCode::NotFound),
+            "too_many_requests" => Ok(ErrorCode::TooManyRequests),
+            "internal" => Ok(ErrorCode::Internal),
+            _ => Err(format!("unknown ErrorCode: {}", value)),
+        }
+    }
+}
+#[sourcegen::generated]
+impl std::convert::TryFrom<u32> for ErrorCode {
+    type Error = String;
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            404 => Ok(ErrorCode::NotFound),
+            429 => Ok(ErrorCode::TooManyRequests),
+            500 => Ok(ErrorCode::Internal),
+            _ => Err(format!("invalid ErrorCode: {}", value)),
+        }
+    }
+}
+
+#[sourcegen::sourcegen(generator = "csv-enum", table = "permissions.tsv", doc = "label", derive = "Debug")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+#[derive(Debug)]
+pub enum Permission {
+    /// Can only read
+    ReadOnly,
+    /// Can do anything
+    Admin,
+}
+#[sourcegen::generated]
+impl Permission {
+    /// Name of the variant as it is in the `name` column.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::ReadOnly => "read-only",
+            Permission::Admin => "admin",
+        }
+    }
+}
+#[sourcegen::generated]
+impl std::str::FromStr for Permission {
+    type Err = String;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "read-only" => Ok(Permission::ReadOnly),
+            "admin" => Ok(Permission::Admin),
+            _ => Err(format!("unknown Permission: {}", value)),
+        }
+    }
+}
+#[sourcegen::generated]
+impl std::convert::TryFrom<u32> for Permission {
+    type Error = String;
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Permission::ReadOnly),
+            1 => Ok(Permission::Admin),
+            _ => Err(format!("invalid Permission: {}", value)),
+        }
+    }
+}
@@ -0,0 +1,10 @@
+#[sourcegen::sourcegen(
+    generator = "csv-enum",
+    table = "errors.csv",
+    value = "code",
+    doc = "description"
+)]
+pub enum ErrorCode {}
+
+#[sourcegen::sourcegen(generator = "csv-enum", table = "permissions.tsv", doc = "label", derive = "Debug")]
+pub enum Permission {}
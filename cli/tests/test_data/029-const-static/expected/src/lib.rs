@@ -0,0 +1,7 @@
+#[sourcegen::sourcegen(generator = "generate-name")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+pub const GREETING: &str = "GREETING";
+
+#[sourcegen::sourcegen(generator = "generate-name")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+static FAREWELL: &str = "FAREWELL";
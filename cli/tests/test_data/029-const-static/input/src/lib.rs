@@ -0,0 +1,5 @@
+#[sourcegen::sourcegen(generator = "generate-name")]
+pub const GREETING: &str = "";
+
+#[sourcegen::sourcegen(generator = "generate-name")]
+static FAREWELL: &str = "";
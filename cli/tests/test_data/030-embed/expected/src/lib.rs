@@ -0,0 +1,23 @@
+#[sourcegen::sourcegen(generator = "embed", path = "queries/users.sql")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+/// Query listing the users
+pub const USERS_QUERY: &'static str = r##"SELECT id, name
+FROM users
+WHERE name = "admin" AND tag = '"#'
+"##;
+
+#[sourcegen::sourcegen(generator = "embed", path = "lookup.bin")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+pub static LOOKUP: &[u8] = b"ab\x01\n";
+
+#[sourcegen::sourcegen(generator = "embed", path = "lookup.bin")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+#[cfg(target_endian = "little")]
+pub static LOOKUP_ARRAY: [u8; 4] = *b"ab\x01\n";
+
+pub mod templates {
+    #[sourcegen::sourcegen(generator = "embed", path = "queries/users.sql")]
+    // Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+    pub(crate) const RAW: &[u8; 63] =
+        b"SELECT id, name\nFROM users\nWHERE name = \"admin\" AND tag = '\"#'\n";
+}
@@ -0,0 +1,15 @@
+#[sourcegen::sourcegen(generator = "embed", path = "queries/users.sql")]
+/// Query listing the users
+pub const USERS_QUERY: &'static str = "";
+
+#[sourcegen::sourcegen(generator = "embed", path = "lookup.bin")]
+pub static LOOKUP: &[u8] = &[];
+
+#[sourcegen::sourcegen(generator = "embed", path = "lookup.bin")]
+#[cfg(target_endian = "little")]
+pub static LOOKUP_ARRAY: [u8; 0] = [];
+
+pub mod templates {
+    #[sourcegen::sourcegen(generator = "embed", path = "queries/users.sql")]
+    pub(crate) const RAW: &[u8; 1] = b"";
+}
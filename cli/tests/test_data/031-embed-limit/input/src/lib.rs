@@ -0,0 +1,2 @@
+#[sourcegen::sourcegen(generator = "embed", path = "large.txt", max_size = 8)]
+pub const LARGE: &str = "";
@@ -0,0 +1,9 @@
+unsafe extern "C" {
+    fn f();
+}
+
+#[sourcegen::sourcegen(generator = "generate-simple")]
+// Generated. All manual edits to the block annotated with #[sourcegen...] will be discarded.
+pub struct Simple {
+    pub hello: String,
+}
@@ -0,0 +1,6 @@
+unsafe extern "C" {
+    fn f();
+}
+
+#[sourcegen::sourcegen(generator = "generate-simple")]
+pub struct Simple;
@@ -0,0 +1 @@
+#![sourcegen::sourcegen(generator = "generate-mod-files", duplicate = true)]
@@ -0,0 +1,5 @@
+#[sourcegen::sourcegen(generator = "json-schema", schema = "widget.json", definition = "shape")]
+pub enum Shape {}
+
+#[sourcegen::sourcegen(generator = "json-schema", schema = "widget.json", definition = "circle")]
+pub struct Circle;
@@ -0,0 +1,2 @@
+#[sourcegen::sourcegen(generator = "csv-enum", table = "errors.csv", value = "code")]
+pub enum ErrorCode {}
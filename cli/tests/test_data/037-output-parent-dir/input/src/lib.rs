@@ -0,0 +1,2 @@
+#[sourcegen::sourcegen(generator = "generate-impls", output = "../outside.rs")]
+struct Escaping;
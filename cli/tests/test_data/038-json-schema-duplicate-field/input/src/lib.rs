@@ -0,0 +1,2 @@
+#[sourcegen::sourcegen(generator = "json-schema", schema = "point.json")]
+pub struct Point;
@@ -0,0 +1,2 @@
+#[sourcegen::sourcegen(generator = "json-schema", schema = "size.json")]
+pub enum Size {}
@@ -1,18 +1,66 @@
 extern crate proc_macro;
 
+use proc_macro::{TokenStream, TokenTree};
+
 /// Does nothing (returns item as-is). Needed to remove the attribute that is handled by source generator.
+///
+/// If the invocation redirects generated code into a separate file (via `output = "..."`), the
+/// annotated item is only a stub for the source generator and is removed.
 #[proc_macro_attribute]
-pub fn sourcegen(
-    _attrs: proc_macro::TokenStream,
-    item: proc_macro::TokenStream,
-) -> proc_macro::TokenStream {
-    item
+pub fn sourcegen(attrs: TokenStream, item: TokenStream) -> TokenStream {
+    if has_output(attrs) {
+        TokenStream::new()
+    } else {
+        item
+    }
 }
 
 #[proc_macro_attribute]
-pub fn generated(
-    _attrs: proc_macro::TokenStream,
-    item: proc_macro::TokenStream,
-) -> proc_macro::TokenStream {
+pub fn generated(_attrs: TokenStream, item: TokenStream) -> TokenStream {
     item
 }
+
+/// Does nothing (expands to nothing). Marks generation site for the source generator where there
+/// is no item to attach `#[sourcegen]` attribute to, for example,
+/// `sourcegen::generate! { generator = "my-generator" }`. Generated items follow the invocation.
+///
+/// This is the function-like counterpart of `#[sourcegen]` attribute (they cannot share the name).
+#[proc_macro]
+pub fn generate(_input: TokenStream) -> TokenStream {
+    TokenStream::new()
+}
+
+/// Expands to the expression following the arguments (`sourcegen::expr!(generator = "my-generator";
+/// <expression>)`). Marks generation site for the source generator in the expression position; the
+/// expression is the generated code.
+#[proc_macro]
+pub fn expr(input: TokenStream) -> TokenStream {
+    let mut tokens = input.into_iter();
+    let has_semi = tokens
+        .by_ref()
+        .any(|token| matches!(token, TokenTree::Punct(ref punct) if punct.as_char() == ';'));
+    let expr = tokens.collect::<TokenStream>();
+    if !has_semi || expr.is_empty() {
+        return "compile_error!(\"expression is not generated yet, run the source generator\")"
+            .parse()
+            .unwrap();
+    }
+    expr
+}
+
+/// Check if attribute arguments contain `output = ...`.
+fn has_output(attrs: TokenStream) -> bool {
+    let mut tokens = attrs.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        if let TokenTree::Ident(ref ident) = token {
+            if ident.to_string() == "output" {
+                if let Some(TokenTree::Punct(punct)) = tokens.peek() {
+                    if punct.as_char() == '=' {
+                        return true;
+                    }
+                }
+            }
+        }
+    }
+    false
+}